@@ -11,7 +11,7 @@ use wasm_bindgen_futures::spawn_local;
 use crate::{
     banner::{display_banner, BannerState, InvalidationResult, UiBanner},
     goal::{display_goal, GoalState},
-    results::{display_results, Data, ResultsState},
+    results::{display_results, stopping_rule, Data, ResultsState},
     SimWorker, SimWorkerInput,
 };
 
@@ -165,8 +165,12 @@ impl eframe::App for App {
 
         if let Some(worker_response) = data_update.replace(None) {
             if status.is_running && results.data != Data::Invalidated {
-                results.data = Data::Present(worker_response);
+                results.data = Data::Present(worker_response.data);
+                results.intervals = worker_response.intervals;
                 status.last_data_received = Some(Instant::now());
+                if worker_response.finished {
+                    status.sim_ended();
+                }
             }
         }
 
@@ -239,13 +243,28 @@ impl eframe::App for App {
                         } else {
                             let button = egui::Button::new("Run");
                             if let Some(sim_banner) = banner.current.to_sim_banner() {
-                                if let Some(sim_goal) = goal.to_sim_goal() {
+                                let goal_errors = goal
+                                    .to_sim_goal()
+                                    .map(|sim_goal| sim_banner.validate_goal(&sim_goal))
+                                    .unwrap_or_default();
+                                if !goal_errors.is_empty() {
+                                    let errors = goal_errors
+                                        .iter()
+                                        .map(ToString::to_string)
+                                        .collect::<Vec<_>>();
+                                    ui.add_enabled(false, button)
+                                        .on_disabled_hover_text(format!(
+                                            "Invalid goal:\n{}",
+                                            errors.join("\n")
+                                        ));
+                                } else if let Some(sim_goal) = goal.to_sim_goal() {
                                     if ui.add(button).clicked() {
                                         log!("Run clicked");
                                         bridge.send(SimWorkerInput::Run {
                                             banner: sim_banner,
                                             goal: sim_goal,
                                             target_interval: Duration::from_millis(500),
+                                            stopping_rule: stopping_rule(goal),
                                         });
                                         status.sim_started();
                                         if results.data == Data::Invalidated {
@@ -257,8 +276,17 @@ impl eframe::App for App {
                                         .on_disabled_hover_text("Invalid goal.");
                                 }
                             } else {
+                                let errors = banner
+                                    .current
+                                    .validation_errors()
+                                    .iter()
+                                    .map(ToString::to_string)
+                                    .collect::<Vec<_>>();
                                 ui.add_enabled(false, button)
-                                    .on_disabled_hover_text("Invalid banner.");
+                                    .on_disabled_hover_text(format!(
+                                        "Invalid banner:\n{}",
+                                        errors.join("\n")
+                                    ));
                             }
                         }
                         if let Some((elapsed, num_samples)) = (|| {
@@ -1,5 +1,9 @@
 use egui::{TextStyle, Ui};
-use summon_simulator::{banner::GenericBanner, types::Color};
+use summon_simulator::{
+    banner::{BannerError, BasePoolSizes, GenericBanner, RateModel},
+    cost::OrbCosts,
+    types::Color,
+};
 
 use crate::app::with_colored_dot;
 
@@ -34,6 +38,20 @@ pub struct UiBanner {
 
 impl UiBanner {
     pub fn to_sim_banner(&self) -> Option<GenericBanner> {
+        let banner = self.to_unchecked_sim_banner();
+        if banner.is_valid() {
+            Some(banner)
+        } else {
+            None
+        }
+    }
+
+    /// Everything that keeps the banner from being simulated.
+    pub fn validation_errors(&self) -> Vec<BannerError> {
+        self.to_unchecked_sim_banner().validate()
+    }
+
+    fn to_unchecked_sim_banner(&self) -> GenericBanner {
         let mut focus_sizes = [0; 4];
         let mut fourstar_focus_sizes = [0; 4];
         for unit in &self.units {
@@ -42,17 +60,18 @@ impl UiBanner {
                 fourstar_focus_sizes[unit.color as usize] += 1;
             }
         }
-        let banner = GenericBanner {
+        GenericBanner {
             starting_rates: (self.starting_rates.focus, self.starting_rates.fivestar),
             focus_sizes,
             fourstar_focus_sizes,
+            fourstar_special_rate: 3,
+            fourstar_focus_rate: 3,
             has_spark: self.has_spark,
             has_charges: self.has_focus_charges,
-        };
-        if banner.is_valid() {
-            Some(banner)
-        } else {
-            None
+            rate_model: RateModel::default(),
+            costs: OrbCosts::default(),
+            base_pool_sizes: BasePoolSizes::default(),
+            extra_pools: Vec::new(),
         }
     }
 }
@@ -314,6 +333,9 @@ pub(crate) fn display_banner(ui: &mut Ui, state: &mut BannerState) -> Invalidati
                     });
                 }
             });
+            for error in state.current.validation_errors() {
+                ui.colored_label(ui.visuals().error_fg_color, error.to_string());
+            }
         });
     invalidation_result
 }
@@ -2,8 +2,8 @@ use egui::{text::TextWrapping, TextStyle, Ui, Widget};
 use egui_extras::{Column, TableBuilder};
 use enumset::EnumSet;
 use summon_simulator::{
-    goal::{BudgetGoal, BudgetGoalLimit, Goal, UnitCountGoal, UnitGoal},
-    types::Pool,
+    goal::{BudgetGoal, BudgetGoalLimit, BudgetUnit, Goal, UnitCountGoal, UnitGoal},
+    types::{Pool, Rarity},
 };
 
 use crate::{app::with_colored_dot, banner::UiBanner};
@@ -68,14 +68,18 @@ impl GoalState {
                         color: unit.color,
                         copies: self.single.unit_count_goal,
                         pools,
+                        min_rarity: Rarity::ThreeStar,
                     }],
                     true,
                 )))
             } else {
                 Some(Goal::OrbBudget(BudgetGoal {
-                    color: unit.color,
+                    units: vec![BudgetUnit {
+                        color: unit.color,
+                        pools,
+                        min_rarity: Rarity::ThreeStar,
+                    }],
                     limit: BudgetGoalLimit::OrbCount(self.single.orb_limit),
-                    pools,
                 }))
             }
         } else {
@@ -96,6 +100,7 @@ impl GoalState {
                         } else {
                             EnumSet::from(Pool::Focus)
                         },
+                        min_rarity: Rarity::ThreeStar,
                     })
                 })
                 .collect::<Vec<_>>();
@@ -5,11 +5,14 @@ mod results;
 pub use app::App;
 
 use gloo_console::log;
-use instant::Instant;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use summon_simulator::{
-    banner::GenericBanner, frequency_counter::FrequencyCounter, goal::Goal, sim,
+    banner::GenericBanner,
+    frequency_counter::FrequencyCounter,
+    goal::Goal,
+    sim::{self, StoppingRule},
+    stream::{Granularity, SimStream},
 };
 
 #[derive(Debug)]
@@ -23,15 +26,23 @@ pub enum SimWorkerInput {
         banner: GenericBanner,
         goal: Goal,
         target_interval: Duration,
+        stopping_rule: StoppingRule,
     },
     Stop,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SimWorkerOutput {
+    pub data: FrequencyCounter,
+    /// Confidence intervals of the stopping rule's percentiles.
+    pub intervals: Vec<(u32, u32)>,
+    /// Whether the results are precise enough that the worker stopped.
+    pub finished: bool,
+}
+
 pub struct SimWorker {
-    sim: Option<sim::Sim>,
-    target_interval: Option<Duration>,
+    stream: Option<SimStream>,
     id: Option<gloo_worker::HandlerId>,
-    num_iters: u32,
     running: bool,
 }
 
@@ -40,14 +51,12 @@ impl gloo_worker::Worker for SimWorker {
 
     type Input = SimWorkerInput;
 
-    type Output = FrequencyCounter;
+    type Output = SimWorkerOutput;
 
     fn create(scope: &gloo_worker::WorkerScope<Self>) -> Self {
         let _scope = scope;
         Self {
-            sim: None,
-            target_interval: None,
-            num_iters: 0,
+            stream: None,
             id: None,
             running: false,
         }
@@ -59,26 +68,27 @@ impl gloo_worker::Worker for SimWorker {
                 if !self.running {
                     return;
                 }
-                match (&mut self.sim, self.target_interval, self.id) {
-                    (Some(sim), Some(interval), Some(id)) => {
-                        log!("Performing ", self.num_iters as f64, " iterations.");
-                        let start = Instant::now();
-                        sim.sim(self.num_iters);
-                        let duration = Instant::now().duration_since(start);
-                        log!("Simulation took ", duration.as_secs_f64() * 1000.0, " ms.");
-                        // Don't send back data if the simulation finished too quickly, since
-                        // there will be much less data in this set than there will be in the
-                        // following ones.
-                        if duration.as_secs_f64() * 2.0 > interval.as_secs_f64() {
-                            scope.respond(id, sim.data().clone());
+                match (&mut self.stream, self.id) {
+                    (Some(stream), Some(id)) => {
+                        let Some(data) = stream.next() else {
+                            self.running = false;
+                            return;
+                        };
+                        log!("Simulated ", data.total() as f64, " iterations so far.");
+                        let finished = stream.is_finished();
+                        scope.respond(
+                            id,
+                            SimWorkerOutput {
+                                data,
+                                intervals: stream.percentile_intervals(),
+                                finished,
+                            },
+                        );
+                        if finished {
+                            log!("Results are precise enough, stopping.");
+                            self.running = false;
+                            return;
                         }
-                        // Update number of iterations to aim for the requested result interval
-                        let new_iters = (self.num_iters as f64 * interval.as_secs_f64()
-                            / duration.as_secs_f64())
-                        .round() as u32;
-                        // ...but don't ramp up more than 10x each time, to avoid a very fast
-                        // first iteration resulting in a very imprecise second
-                        self.num_iters = new_iters.min(self.num_iters.saturating_mul(10));
                     }
                     _ => panic!("Received Continue message without parameters"),
                 }
@@ -105,10 +115,13 @@ impl gloo_worker::Worker for SimWorker {
                 banner,
                 goal,
                 target_interval: interval,
+                stopping_rule,
             } => {
-                self.sim = Some(sim::Sim::new(banner, goal));
-                self.num_iters = 100;
-                self.target_interval = Some(interval);
+                self.stream = Some(
+                    sim::Sim::new(banner, goal)
+                        .stream(Granularity::Time(interval))
+                        .with_stopping_rule(stopping_rule),
+                );
                 self.id = Some(id);
                 self.running = true;
                 scope.send_message(SimWorkerMessage::Continue);
@@ -1,7 +1,7 @@
 use std::fmt::Write;
 
 use egui::{Ui, Widget};
-use summon_simulator::frequency_counter::FrequencyCounter;
+use summon_simulator::{frequency_counter::FrequencyCounter, sim::StoppingRule};
 
 use crate::{banner::UiBanner, goal::GoalState};
 
@@ -32,6 +32,8 @@ pub struct ResultsState {
     pub data: Data,
     pub typ: DisplayType,
     pub percentile_slider: u32,
+    /// Confidence intervals for each of [`SAMPLE_PERCENTILES`].
+    pub intervals: Vec<(u32, u32)>,
 }
 
 impl ResultsState {
@@ -40,6 +42,7 @@ impl ResultsState {
             data: Data::Invalidated,
             typ: DisplayType::Text,
             percentile_slider: 500,
+            intervals: Vec::new(),
         }
     }
 }
@@ -81,19 +84,55 @@ fn percentiles(data: &FrequencyCounter, values: &[f32], invert: bool) -> Vec<u32
     result
 }
 
-fn data_percentiles_to_string(data: &FrequencyCounter, data_label: &str, invert: bool) -> String {
-    let sample_percentiles = [0.25f32, 0.5, 0.75, 0.9, 0.99];
-    let data = percentiles(data, &sample_percentiles, invert);
+const SAMPLE_PERCENTILES: [f32; 5] = [0.25, 0.5, 0.75, 0.9, 0.99];
+
+fn is_orb_goal(goal: &GoalState) -> bool {
+    goal.is_single && !goal.single.is_quantity_goal
+}
+
+/// Stop simulating once every displayed percentile is known to within one summon's worth of
+/// orbs, or exactly for numbers of copies.
+pub fn stopping_rule(goal: &GoalState) -> StoppingRule {
+    let is_orb_goal = is_orb_goal(goal);
+    StoppingRule {
+        percentiles: SAMPLE_PERCENTILES
+            .iter()
+            .map(|&p| {
+                if is_orb_goal {
+                    1.0 - p as f64
+                } else {
+                    p as f64
+                }
+            })
+            .collect(),
+        max_width: if is_orb_goal { 0 } else { 5 },
+        ..Default::default()
+    }
+}
+
+fn data_percentiles_to_string(
+    data: &FrequencyCounter,
+    data_label: &str,
+    invert: bool,
+    intervals: &[(u32, u32)],
+) -> String {
+    let data = percentiles(data, &SAMPLE_PERCENTILES, invert);
     let mut output = String::new();
-    for i in 0..sample_percentiles.len() {
-        writeln!(
+    for i in 0..SAMPLE_PERCENTILES.len() {
+        write!(
             &mut output,
             "{}%: {} {}",
-            (sample_percentiles[i] * 100.0).round() as u32,
+            (SAMPLE_PERCENTILES[i] * 100.0).round() as u32,
             data[i],
             data_label
         )
         .unwrap();
+        match intervals.get(i) {
+            Some(&(low, high)) if low != high => {
+                writeln!(&mut output, " (95% confidence: {}-{})", low, high).unwrap()
+            }
+            _ => writeln!(&mut output).unwrap(),
+        }
     }
     output
 }
@@ -123,7 +162,7 @@ fn display_text_results(
     goal: &GoalState,
     results: &mut ResultsState,
 ) {
-    let is_orb_goal = goal.is_single && !goal.single.is_quantity_goal;
+    let is_orb_goal = is_orb_goal(goal);
     let label = if is_orb_goal {
         "copies or more"
     } else {
@@ -131,7 +170,12 @@ fn display_text_results(
     };
     match &results.data {
         Data::Present(data) => {
-            ui.label(data_percentiles_to_string(data, label, is_orb_goal));
+            ui.label(data_percentiles_to_string(
+                data,
+                label,
+                is_orb_goal,
+                &results.intervals,
+            ));
             ui.horizontal(|ui| {
                 if ui.button("-0.1%").clicked() {
                     results.percentile_slider -= 1;
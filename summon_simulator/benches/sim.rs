@@ -3,8 +3,8 @@ use enumset::EnumSet;
 use summon_simulator::{
     banner::StandardBanner,
     goal::{UnitCountGoal, UnitGoal},
-    sim::sim_until_goal_many,
-    types::{Color, Pool},
+    sim::{sim_until_goal_many, SimConfig},
+    types::{Color, Pool, Rarity},
 };
 
 fn sim_benchmark(c: &mut Criterion) {
@@ -17,15 +17,17 @@ fn sim_benchmark(c: &mut Criterion) {
             color: Color::Red,
             copies: 1,
             pools: EnumSet::from(Pool::Focus),
+            min_rarity: Rarity::ThreeStar,
         }],
         true,
     );
+    let config = SimConfig::default();
     c.bench_function("standard_one_red_focus_10kx", |b| {
-        b.iter(|| sim_until_goal_many(&banner, &goal, 10000))
+        b.iter(|| sim_until_goal_many(&banner, &goal, &config, 10000, 0))
     });
     // To test competitiveness with the old version, which completes 200k iterations in 1-2s on WASM
     // c.bench_function("standard_one_red_focus_200kx", |b| {
-    //     b.iter(|| sim_until_goal_many(&banner, &goal, 200000))
+    //     b.iter(|| sim_until_goal_many(&banner, &goal, &config, 200000, 0))
     // });
 }
 
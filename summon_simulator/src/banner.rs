@@ -1,6 +1,13 @@
+use std::fmt;
+
+use enumset::EnumSet;
 use serde::{Deserialize, Serialize};
 
-use crate::types::Pool;
+use crate::{
+    cost::OrbCosts,
+    goal::Goal,
+    types::{Color, Pool, Rarity},
+};
 
 #[derive(Copy, Clone, Debug)]
 pub enum StandardBanner {
@@ -18,7 +25,40 @@ pub enum StandardBanner {
     WeeklyRevival {
         focus: [u8; 4],
     },
+    /// A weekly focus with its own rates, like the ones for Tempest Trials, as (focus, off-focus)
+    /// 5★ rates in percent.
+    WeeklyFocus {
+        focus: [u8; 4],
+        rates: (u8, u8),
+    },
+    /// The banners for Attuned, Rearmed, Ascended and Emblem Heroes. They have the New Heroes
+    /// rates of 3% focus and 3% off-focus 5★, with a spark and focus charges whether or not the
+    /// player has FEH Pass, and some of them have a 4★ focus unit.
+    Attuned {
+        focus: [u8; 4],
+        fourstar_focus: [u8; 4],
+    },
+    Rearmed {
+        focus: [u8; 4],
+        fourstar_focus: [u8; 4],
+    },
+    Ascended {
+        focus: [u8; 4],
+        fourstar_focus: [u8; 4],
+    },
+    Emblem {
+        focus: [u8; 4],
+        fourstar_focus: [u8; 4],
+    },
+    /// A revival of seasonal Special Heroes, which gets a spark and focus charges only with FEH
+    /// Pass like the original banners.
+    SpecialRevival {
+        focus: [u8; 4],
+        fourstar_focus: [u8; 4],
+    },
     Legendary,
+    /// A Legendary or Mythic banner from before 4★ specials were added to them.
+    LegendaryLegacy,
     HeroFest,
     LegendaryRemix,
     DoubleSpecial {
@@ -29,61 +69,107 @@ pub enum StandardBanner {
 impl StandardBanner {
     pub fn as_generic_banner(&self, has_feh_pass: bool) -> GenericBanner {
         use StandardBanner::*;
+        // New Heroes rates with no units picked; each banner type changes what differs
+        let base = GenericBanner {
+            starting_rates: (3, 3),
+            focus_sizes: [0, 0, 0, 0],
+            fourstar_focus_sizes: [0, 0, 0, 0],
+            fourstar_special_rate: 3,
+            fourstar_focus_rate: 3,
+            has_spark: false,
+            has_charges: true,
+            rate_model: RateModel::default(),
+            costs: OrbCosts::default(),
+            base_pool_sizes: BasePoolSizes::default(),
+            extra_pools: Vec::new(),
+        };
         match *self {
             Standard { focus } => GenericBanner {
-                starting_rates: (3, 3),
                 focus_sizes: focus,
-                fourstar_focus_sizes: [0, 0, 0, 0],
-                has_spark: false,
-                has_charges: true,
+                ..base
             },
             NewHeroes {
                 focus,
                 fourstar_focus,
             } => GenericBanner {
-                starting_rates: (3, 3),
                 focus_sizes: focus,
                 fourstar_focus_sizes: fourstar_focus,
                 has_spark: true,
-                has_charges: true,
+                ..base
             },
             NewSeasonal {
                 focus,
                 fourstar_focus,
+            }
+            | SpecialRevival {
+                focus,
+                fourstar_focus,
             } => GenericBanner {
-                starting_rates: (3, 3),
                 focus_sizes: focus,
                 fourstar_focus_sizes: fourstar_focus,
                 has_spark: has_feh_pass,
                 has_charges: has_feh_pass,
+                ..base
             },
             WeeklyRevival { focus } => GenericBanner {
                 starting_rates: (4, 2),
                 focus_sizes: focus,
-                fourstar_focus_sizes: [0, 0, 0, 0],
-                has_spark: false,
-                has_charges: true,
+                ..base
+            },
+            WeeklyFocus { focus, rates } => GenericBanner {
+                starting_rates: rates,
+                focus_sizes: focus,
+                ..base
+            },
+            Attuned {
+                focus,
+                fourstar_focus,
+            }
+            | Rearmed {
+                focus,
+                fourstar_focus,
+            }
+            | Ascended {
+                focus,
+                fourstar_focus,
+            }
+            | Emblem {
+                focus,
+                fourstar_focus,
+            } => GenericBanner {
+                focus_sizes: focus,
+                fourstar_focus_sizes: fourstar_focus,
+                has_spark: true,
+                ..base
             },
             Legendary => GenericBanner {
                 starting_rates: (8, 0),
                 focus_sizes: [3, 3, 3, 3],
-                fourstar_focus_sizes: [0, 0, 0, 0],
                 has_spark: has_feh_pass,
                 has_charges: false,
+                ..base
+            },
+            LegendaryLegacy => GenericBanner {
+                starting_rates: (8, 0),
+                focus_sizes: [3, 3, 3, 3],
+                fourstar_special_rate: 0,
+                has_spark: has_feh_pass,
+                has_charges: false,
+                ..base
             },
             HeroFest => GenericBanner {
                 starting_rates: (5, 3),
                 focus_sizes: [1, 1, 1, 1],
-                fourstar_focus_sizes: [0, 0, 0, 0],
                 has_spark: has_feh_pass,
                 has_charges: has_feh_pass,
+                ..base
             },
             LegendaryRemix => GenericBanner {
                 starting_rates: (6, 0),
                 focus_sizes: [2, 2, 2, 2],
-                fourstar_focus_sizes: [0, 0, 0, 0],
                 has_spark: true,
                 has_charges: false,
+                ..base
             },
             DoubleSpecial { fourstar_focus } => GenericBanner {
                 starting_rates: (6, 0),
@@ -91,6 +177,7 @@ impl StandardBanner {
                 fourstar_focus_sizes: fourstar_focus,
                 has_spark: false,
                 has_charges: false,
+                ..base
             },
         }
     }
@@ -101,56 +188,460 @@ pub struct GenericBanner {
     pub starting_rates: (u8, u8),
     pub focus_sizes: [u8; 4],
     pub fourstar_focus_sizes: [u8; 4],
+    /// Starting rate of 4★ special units, in percent.
+    #[serde(default = "default_fourstar_special_rate")]
+    pub fourstar_special_rate: u8,
+    /// Starting rate of 4★ focus units, in percent, if the banner has any.
+    #[serde(default = "default_fourstar_focus_rate")]
+    pub fourstar_focus_rate: u8,
     pub has_spark: bool,
     pub has_charges: bool,
+    #[serde(default)]
+    pub rate_model: RateModel,
+    #[serde(default)]
+    pub costs: OrbCosts,
+    /// Units of each color in the pools that aren't specific to the banner.
+    #[serde(default)]
+    pub base_pool_sizes: BasePoolSizes,
+    /// Pools on top of the five that every banner has, each with its own rate, pity and charge
+    /// effects, e.g. a second focus pool. Their rates come out of the common pool's.
+    ///
+    /// Goals still pick units by [`Pool`] kind, so an extra pool adds units to one of the five
+    /// kinds rather than making a new one. A pool whose units need goals of their own, like a
+    /// Grand Hero Battle pool or a separate 4★ special focus, needs a new `Pool` variant.
+    #[serde(default)]
+    pub extra_pools: Vec<PoolSpec>,
+}
+
+fn default_fourstar_special_rate() -> u8 {
+    3
+}
+
+fn default_fourstar_focus_rate() -> u8 {
+    3
+}
+
+/// What pulling a unit from a pool does to the pity rate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PityEffect {
+    /// Back to the starting rates, like a focus 5★.
+    Reset,
+    /// Takes away some pity, like an off-focus 5★.
+    Reduce,
+    /// Adds a summon's worth of pity.
+    Increase,
+}
+
+/// What pulling a unit from a pool does to focus charges.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChargeEffect {
+    None,
+    /// Earns a focus charge, like an off-focus 5★.
+    Gain,
+    /// Uses up the focus charges once they're active, like a focus 5★.
+    Spend,
+}
+
+/// One of the pools that a banner's stones come from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PoolSpec {
+    /// What kind of pool this is, which decides the rarity of its units and which goals they
+    /// count for. The units of every pool of the same kind are numbered one after another, in
+    /// the order the banner lists the pools, so goals can't tell which of them a unit came
+    /// from.
+    pub kind: Pool,
+    /// Starting rate, in percent.
+    pub rate: u8,
+    /// Units of each color.
+    pub sizes: [u8; 4],
+    pub pity: PityEffect,
+    pub charges: ChargeEffect,
+}
+
+impl PoolSpec {
+    /// Whether every unit of the pool is a 5★, which makes the pool's rate go up with pity.
+    pub fn is_fivestar(&self) -> bool {
+        self.kind.fixed_rarity() == Some(Rarity::FiveStar)
+    }
+}
+
+/// How the rates of a banner change as it's pulled on. The default is how the game works
+/// today.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct RateModel {
+    /// Summons without a focus 5★ needed for each step of pity.
+    pub pity_step_pulls: u32,
+    /// How much each step of pity raises the combined 5★ rate, in hundredths of a percent.
+    pub pity_step_increase: u32,
+    /// Once pity reaches this many steps, the next summon is guaranteed to be a 5★.
+    pub max_pity_steps: u32,
+    /// Summons' worth of pity taken away by an off-focus 5★.
+    pub off_focus_pity_reset: u32,
+    /// Off-focus 5★s needed to activate focus charges.
+    pub charge_threshold: u32,
+    /// Summons needed to earn a spark.
+    pub spark_threshold: u32,
+    /// Whether another spark is earned every `spark_threshold` summons, rather than only once.
+    pub spark_repeats: bool,
+    /// Starting rate of 3★ units, in percent. It comes out of the common pool, and the rest of
+    /// the common pool is 4★.
+    pub common_threestar_rate: u32,
+}
+
+impl Default for RateModel {
+    fn default() -> Self {
+        Self {
+            pity_step_pulls: 5,
+            pity_step_increase: 50,
+            max_pity_steps: 24,
+            off_focus_pity_reset: 20,
+            charge_threshold: 3,
+            spark_threshold: 40,
+            spark_repeats: false,
+            common_threestar_rate: 36,
+        }
+    }
+}
+
+/// Units of each color in the pools that every banner shares, which change as new units are
+/// added to the game. The default is the game's current pools.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct BasePoolSizes {
+    /// Off-focus 5★ units.
+    pub fivestar: [u8; 4],
+    /// 4★ special units.
+    pub fourstar_special: [u8; 4],
+    /// Units that can be pulled at 3★ or 4★.
+    pub common: [u8; 4],
+}
+
+impl Default for BasePoolSizes {
+    fn default() -> Self {
+        Self {
+            fivestar: [29, 29, 20, 16],
+            fourstar_special: [62, 41, 36, 28],
+            common: [41, 44, 35, 44],
+        }
+    }
+}
+
+impl RateModel {
+    /// Steps of pity reached after `pity_count` summons without a focus 5★.
+    pub fn pity_steps(&self, pity_count: u32) -> u32 {
+        (pity_count / self.pity_step_pulls).min(self.max_pity_steps)
+    }
 }
 
 impl GenericBanner {
+    /// Units of each color in all of the banner's pools of kind `pool`.
     pub fn pool_sizes(&self, pool: Pool) -> [u8; 4] {
-        use Pool::*;
-        match pool {
-            Focus => self.focus_sizes,
-            Fivestar => [29, 29, 20, 16],
-            FourstarFocus => self.fourstar_focus_sizes,
-            FourstarSpecial => [62, 41, 36, 28],
-            Common => [41, 44, 35, 44],
+        let mut sizes = [0; 4];
+        for spec in self.pools().iter().filter(|spec| spec.kind == pool) {
+            for (size, spec_size) in sizes.iter_mut().zip(spec.sizes) {
+                *size += spec_size;
+            }
         }
+        sizes
     }
 
-    pub fn starting_rates(&self) -> [u8; 5] {
-        let focus_rate = self.starting_rates.0;
-        let fivestar_rate = self.starting_rates.1;
+    /// Combined starting rate of all of the banner's pools of kind `pool`, in percent.
+    pub fn pool_rate(&self, pool: Pool) -> u8 {
+        self.pools()
+            .iter()
+            .filter(|spec| spec.kind == pool)
+            .map(|spec| spec.rate)
+            .sum()
+    }
+
+    /// Every pool that the banner's stones come from: the focus, off-focus 5★, 4★ focus, 4★
+    /// special and common pools, then the extra pools. The common pool gets whatever rate the
+    /// others leave over.
+    pub fn pools(&self) -> Vec<PoolSpec> {
+        use ChargeEffect as C;
+        use PityEffect as P;
+        let pool = |kind, rate, sizes, pity, charges| PoolSpec {
+            kind,
+            rate,
+            sizes,
+            pity,
+            charges,
+        };
         let fourstar_focus_rate = if self.fourstar_focus_sizes != [0, 0, 0, 0] {
-            3
+            self.fourstar_focus_rate
         } else {
             0
         };
-        let fourstar_special_rate = 3;
+        let mut pools = vec![
+            pool(
+                Pool::Focus,
+                self.starting_rates.0,
+                self.focus_sizes,
+                P::Reset,
+                C::Spend,
+            ),
+            pool(
+                Pool::Fivestar,
+                self.starting_rates.1,
+                self.base_pool_sizes.fivestar,
+                P::Reduce,
+                C::Gain,
+            ),
+            pool(
+                Pool::FourstarFocus,
+                fourstar_focus_rate,
+                self.fourstar_focus_sizes,
+                P::Increase,
+                C::None,
+            ),
+            pool(
+                Pool::FourstarSpecial,
+                self.fourstar_special_rate,
+                self.base_pool_sizes.fourstar_special,
+                P::Increase,
+                C::None,
+            ),
+        ];
+        pools.extend_from_slice(&self.extra_pools);
         let common_rate =
-            100 - focus_rate - fivestar_rate - fourstar_focus_rate - fourstar_special_rate;
-        [
-            focus_rate,
-            fivestar_rate,
-            fourstar_focus_rate,
-            fourstar_special_rate,
-            common_rate,
-        ]
+            100u32.saturating_sub(pools.iter().map(|spec| spec.rate as u32).sum()) as u8;
+        pools.insert(
+            4,
+            pool(
+                Pool::Common,
+                common_rate,
+                self.base_pool_sizes.common,
+                P::Increase,
+                C::None,
+            ),
+        );
+        pools
     }
 
-    pub fn is_valid(&self) -> bool {
-        if self.starting_rates.0.saturating_add(self.starting_rates.1) > 100
-            || self.starting_rates.0 == 0
-        {
-            return false;
-        }
-        for i in 0..4 {
-            if self.fourstar_focus_sizes[i] > self.focus_sizes[i] {
-                return false;
+    /// Sparks earned after `total_pulled` summons.
+    pub fn sparks_earned(&self, total_pulled: u32) -> u32 {
+        let threshold = self.rate_model.spark_threshold;
+        if !self.has_spark || threshold == 0 {
+            0
+        } else if self.rate_model.spark_repeats {
+            total_pulled / threshold
+        } else {
+            (total_pulled >= threshold) as u32
+        }
+    }
+
+    /// Fraction of the units pulled from the common pool that are 3★.
+    pub fn common_threestar_share(&self) -> f64 {
+        let common_rate = self.pool_rate(Pool::Common) as u32;
+        if common_rate == 0 {
+            return 0.0;
+        }
+        self.rate_model.common_threestar_rate.min(common_rate) as f64 / common_rate as f64
+    }
+
+    /// Chance that a unit pulled from `pool` is at least `min_rarity`.
+    pub fn rarity_chance(&self, pool: Pool, min_rarity: Rarity) -> f64 {
+        match (pool.fixed_rarity(), min_rarity) {
+            (Some(rarity), _) => (rarity >= min_rarity) as u8 as f64,
+            (None, Rarity::ThreeStar) => 1.0,
+            (None, Rarity::FourStar) => 1.0 - self.common_threestar_share(),
+            (None, Rarity::FiveStar) => 0.0,
+        }
+    }
+
+    /// Every problem that makes the banner impossible to simulate.
+    pub fn validate(&self) -> Vec<BannerError> {
+        let mut errors = Vec::new();
+        let pools = self.pools();
+        // The common pool only takes up rates that the others leave over, so the total is only
+        // over 100% when theirs is
+        let total = pools.iter().map(|spec| spec.rate as u32).sum::<u32>();
+        if total > 100 {
+            errors.push(BannerError::RatesOver100 { total });
+        }
+        let fivestar_total = pools
+            .iter()
+            .filter(|spec| spec.is_fivestar())
+            .map(|spec| spec.rate as u32)
+            .sum::<u32>();
+        if fivestar_total >= 100 {
+            errors.push(BannerError::NoRateForPity { fivestar_total });
+        }
+        if pools[0].rate == 0 {
+            errors.push(BannerError::ZeroFocusRate);
+        }
+        for color in EnumSet::<Color>::all() {
+            let focus = self.focus_sizes[color as usize];
+            let fourstar_focus = self.fourstar_focus_sizes[color as usize];
+            if fourstar_focus > focus {
+                errors.push(BannerError::TooManyFourstarFocus {
+                    color,
+                    fourstar_focus,
+                    focus,
+                });
             }
         }
         if self.focus_sizes == [0, 0, 0, 0] {
-            return false;
+            errors.push(BannerError::EmptyFocusPool);
+        }
+        let rate_model = &self.rate_model;
+        if rate_model.pity_step_pulls == 0 {
+            errors.push(BannerError::ZeroPityStepPulls);
+        }
+        if self.has_charges && rate_model.charge_threshold == 0 {
+            errors.push(BannerError::ZeroChargeThreshold);
+        }
+        if self.has_spark && rate_model.spark_threshold == 0 {
+            errors.push(BannerError::ZeroSparkThreshold);
+        }
+        // The focus pool has its own error
+        for spec in &pools[1..] {
+            if spec.rate > 0 && spec.sizes == [0, 0, 0, 0] {
+                errors.push(BannerError::EmptyPool { pool: spec.kind });
+            }
+        }
+        errors
+    }
+
+    /// Every problem with `goal` on this banner, not counting the banner's own problems.
+    pub fn validate_goal(&self, goal: &Goal) -> Vec<BannerError> {
+        let units: Vec<(Color, EnumSet<Pool>, Rarity)> = match goal {
+            Goal::Quantity(goal) => goal
+                .units
+                .iter()
+                .filter(|unit| unit.copies > 0)
+                .map(|unit| (unit.color, unit.pools, unit.min_rarity))
+                .collect(),
+            Goal::OrbBudget(goal) => goal
+                .units
+                .iter()
+                .map(|unit| (unit.color, unit.pools, unit.min_rarity))
+                .collect(),
+        };
+        let mut missing = EnumSet::new();
+        let mut too_rare = Vec::new();
+        for (color, pools, min_rarity) in units {
+            let mut available = pools
+                .iter()
+                .filter(|&pool| self.pool_sizes(pool)[color as usize] > 0)
+                .peekable();
+            if available.peek().is_none() {
+                missing |= color;
+            } else if available.all(|pool| self.rarity_chance(pool, min_rarity) == 0.0)
+                && !too_rare.contains(&(color, min_rarity))
+            {
+                too_rare.push((color, min_rarity));
+            }
+        }
+        missing
+            .iter()
+            .map(|color| BannerError::NoGoalUnits { color })
+            .chain(
+                too_rare
+                    .into_iter()
+                    .map(|(color, min_rarity)| BannerError::UnreachableRarity {
+                        color,
+                        min_rarity,
+                    }),
+            )
+            .collect()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.validate().is_empty()
+    }
+}
+
+/// A problem with a banner, or with a goal for it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BannerError {
+    /// The starting rates of all pools other than the common pool add up to more than 100%.
+    RatesOver100 {
+        total: u32,
+    },
+    /// The 5★ pools' starting rates add up to 100% or more, so there's no rate left for pity to
+    /// move to them.
+    NoRateForPity {
+        fivestar_total: u32,
+    },
+    ZeroFocusRate,
+    /// A color has more 4★ focus units than focus units. Every 4★ focus unit is also one of the
+    /// focus units.
+    TooManyFourstarFocus {
+        color: Color,
+        fourstar_focus: u8,
+        focus: u8,
+    },
+    EmptyFocusPool,
+    /// The rate model's `pity_step_pulls` is 0, so pity steps can't be counted.
+    ZeroPityStepPulls,
+    /// The banner has focus charges, but they'd be active without earning any.
+    ZeroChargeThreshold,
+    /// The banner has a spark, but it would take no summons to earn.
+    ZeroSparkThreshold,
+    /// A pool other than the focus pool has a rate but no units.
+    EmptyPool {
+        pool: Pool,
+    },
+    /// The goal wants a unit of a color that has no units in any of the pools it can come from.
+    NoGoalUnits {
+        color: Color,
+    },
+    /// The goal wants a unit of at least `min_rarity`, but none of the pools it can come from
+    /// have units that rare.
+    UnreachableRarity {
+        color: Color,
+        min_rarity: Rarity,
+    },
+}
+
+impl fmt::Display for BannerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BannerError::RatesOver100 { total } => {
+                write!(f, "The starting rates add up to {total}%, over 100%.")
+            }
+            BannerError::NoRateForPity { fivestar_total } => write!(
+                f,
+                "The 5★ starting rates add up to {fivestar_total}%, leaving no rate for pity to \
+                take."
+            ),
+            BannerError::ZeroFocusRate => write!(f, "The focus rate is 0%."),
+            BannerError::TooManyFourstarFocus {
+                color,
+                fourstar_focus,
+                focus,
+            } => write!(
+                f,
+                "{color:?} has {fourstar_focus} 4★ focus units but only {focus} focus units."
+            ),
+            BannerError::EmptyFocusPool => write!(f, "The banner has no focus units."),
+            BannerError::ZeroPityStepPulls => {
+                write!(f, "Pity goes up every 0 summons.")
+            }
+            BannerError::ZeroChargeThreshold => {
+                write!(f, "Focus charges need 0 off-focus 5★ units to activate.")
+            }
+            BannerError::ZeroSparkThreshold => write!(f, "The spark needs 0 summons."),
+            BannerError::EmptyPool { pool } => {
+                write!(f, "A {pool:?} pool has a rate but no units.")
+            }
+            BannerError::NoGoalUnits { color } => {
+                write!(
+                    f,
+                    "The goal wants a {color:?} unit, but the banner has none."
+                )
+            }
+            BannerError::UnreachableRarity { color, min_rarity } => {
+                write!(
+                    f,
+                    "The goal wants a {color:?} unit at {min_rarity:?} or above, but it can't be \
+                    pulled that rare."
+                )
+            }
         }
-        true
     }
 }
+
+impl std::error::Error for BannerError {}
@@ -0,0 +1,188 @@
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro128Plus;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    banner::GenericBanner,
+    frequency_counter::FrequencyCounter,
+    goal::UnitCountGoal,
+    sim::{sim_until_goal, DistributionCache, SimConfig},
+};
+
+/// When to give up on a step of a campaign and move on to the next one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StepStop {
+    /// Keep pulling until the goal is reached or the orbs run out.
+    Goal,
+    /// Keep pulling until the goal is reached or this many orbs have been spent on the step.
+    GoalOrOrbs(u32),
+}
+
+#[derive(Clone, Debug)]
+pub struct CampaignStep {
+    pub banner: GenericBanner,
+    pub goal: UnitCountGoal,
+    pub stop: StepStop,
+    pub config: SimConfig,
+}
+
+/// Several banners pulled on one after another, all paid for out of the same orbs.
+#[derive(Clone, Debug)]
+pub struct Campaign {
+    pub steps: Vec<CampaignStep>,
+    pub orbs: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CampaignResults {
+    pub trials: u32,
+    /// How many trials reached the goal of each step.
+    pub step_successes: Vec<u32>,
+    /// How many trials reached the goals of every step.
+    pub all_successes: u32,
+    /// Orbs spent on each step.
+    pub step_orbs_spent: Vec<FrequencyCounter>,
+    /// Orbs left after the last step.
+    pub orbs_left: FrequencyCounter,
+}
+
+impl CampaignResults {
+    pub fn new(num_steps: usize) -> Self {
+        Self {
+            trials: 0,
+            step_successes: vec![0; num_steps],
+            all_successes: 0,
+            step_orbs_spent: vec![FrequencyCounter::new(); num_steps],
+            orbs_left: FrequencyCounter::new(),
+        }
+    }
+
+    pub fn step_success_rate(&self, step: usize) -> f64 {
+        self.step_successes[step] as f64 / self.trials as f64
+    }
+
+    pub fn all_success_rate(&self) -> f64 {
+        self.all_successes as f64 / self.trials as f64
+    }
+
+    pub fn combine(&mut self, other: CampaignResults) {
+        self.trials += other.trials;
+        self.all_successes += other.all_successes;
+        for (successes, other) in self.step_successes.iter_mut().zip(other.step_successes) {
+            *successes += other;
+        }
+        for (orbs_spent, other) in self.step_orbs_spent.iter_mut().zip(other.step_orbs_spent) {
+            orbs_spent.combine(other);
+        }
+        self.orbs_left.combine(other.orbs_left);
+    }
+}
+
+impl Campaign {
+    /// Runs `iters` trials of the whole campaign. A step that runs out of orbs counts as failed,
+    /// and the next step gets whatever is left.
+    pub fn sim(&self, iters: u32, seed: u64) -> CampaignResults {
+        let mut results = CampaignResults::new(self.steps.len());
+        let caches = self
+            .steps
+            .iter()
+            .map(|step| DistributionCache::new(&step.banner))
+            .collect::<Vec<_>>();
+        let mut rng = Xoshiro128Plus::seed_from_u64(seed);
+        for _ in 0..iters {
+            let mut orbs_left = self.orbs;
+            let mut all_finished = true;
+            for (i, (step, cache)) in self.steps.iter().zip(caches.iter()).enumerate() {
+                // The trial's orbs spent include any the step's start state had already spent
+                let start_orbs = step.config.start.status.orbs_spent;
+                let orb_limit = match step.stop {
+                    StepStop::Goal => orbs_left,
+                    StepStop::GoalOrOrbs(limit) => limit.min(orbs_left),
+                };
+                let mut goal = step.goal.clone();
+                let metrics = sim_until_goal(
+                    &step.banner,
+                    &mut goal,
+                    start_orbs.saturating_add(orb_limit),
+                    &step.config,
+                    &mut rng,
+                    cache,
+                    &mut (),
+                );
+                let orbs_spent = metrics.orbs_spent.saturating_sub(start_orbs);
+                orbs_left = orbs_left.saturating_sub(orbs_spent);
+                results.step_orbs_spent[i][orbs_spent] += 1;
+                if goal.finished() {
+                    results.step_successes[i] += 1;
+                } else {
+                    all_finished = false;
+                }
+            }
+            results.trials += 1;
+            if all_finished {
+                results.all_successes += 1;
+            }
+            results.orbs_left[orbs_left] += 1;
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sim::test_banner_and_goal;
+
+    use super::*;
+
+    fn step(stop: StepStop) -> CampaignStep {
+        let (mut banner, goal) = test_banner_and_goal();
+        banner.has_spark = true;
+        CampaignStep {
+            banner,
+            goal,
+            stop,
+            config: SimConfig::default(),
+        }
+    }
+
+    #[test]
+    fn test_campaign() {
+        let campaign = Campaign {
+            steps: vec![step(StepStop::Goal), step(StepStop::Goal)],
+            orbs: 300,
+        };
+        let results = campaign.sim(10000, 0);
+        assert_eq!(results.trials, 10000);
+        // The spark guarantees the first unit within 300 orbs
+        assert_eq!(results.step_successes[0], 10000);
+        assert!(results.step_successes[1] < 10000);
+        assert_eq!(results.all_successes, results.step_successes[1]);
+        assert_eq!(results.orbs_left.iter().sum::<u32>(), 10000);
+        assert!(results.orbs_left.len() <= 301);
+
+        let capped = Campaign {
+            steps: vec![step(StepStop::GoalOrOrbs(20)), step(StepStop::Goal)],
+            orbs: 300,
+        };
+        let capped_results = capped.sim(10000, 0);
+        assert!(capped_results.step_successes[0] < 10000);
+        assert!(capped_results.step_orbs_spent[0].len() <= 21);
+        assert!(capped_results.step_successes[1] > results.step_successes[1]);
+    }
+
+    #[test]
+    fn test_campaign_start_state() {
+        // Orbs spent before the campaign don't come out of its orbs
+        let mut started = step(StepStop::GoalOrOrbs(20));
+        started.config.start.status.orbs_spent = 100;
+        let campaign = Campaign {
+            steps: vec![started, step(StepStop::Goal)],
+            orbs: 50,
+        };
+        let results = campaign.sim(1000, 0);
+        assert!(results.step_orbs_spent[0].len() <= 21);
+        assert!(results.step_orbs_spent[0].len() >= 5);
+        assert!(results.orbs_left.len() <= 51);
+        assert_eq!(results.orbs_left.iter().sum::<u32>(), 1000);
+    }
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+
+/// What each summon of a session costs in orbs. The default is the game's usual costs, and
+/// discount events can be modelled by changing them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrbCosts {
+    /// Cost of each summon of a session, in the order they're pulled.
+    pub session: [u32; 5],
+}
+
+impl Default for OrbCosts {
+    fn default() -> Self {
+        Self {
+            session: [5, 4, 4, 4, 3],
+        }
+    }
+}
+
+impl OrbCosts {
+    /// Every summon costs the same, no matter how many were pulled before it.
+    pub fn flat(cost: u32) -> Self {
+        Self { session: [cost; 5] }
+    }
+
+    /// Changes the cost of the first summon of each session, e.g. for a discounted first summon.
+    pub fn with_first_summon(mut self, cost: u32) -> Self {
+        self.session[0] = cost;
+        self
+    }
+
+    /// Orbs for the next summon, with `num_pulled` stones already pulled this session.
+    pub fn cost(&self, num_pulled: u32) -> u32 {
+        self.session[num_pulled as usize]
+    }
+
+    /// Orbs for pulling every stone of a session.
+    pub fn max_session_cost(&self) -> u32 {
+        self.session.iter().sum()
+    }
+}
+
+/// Which summons of a session a free summon can be spent on.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FreeSummonUse {
+    /// Only the first summon of a session, like the game's free summons and summoning tickets.
+    #[default]
+    FirstOfSession,
+    /// Any summon.
+    AnySummon,
+}
+
+impl FreeSummonUse {
+    pub fn allows(&self, num_pulled: u32) -> bool {
+        match self {
+            FreeSummonUse::FirstOfSession => num_pulled == 0,
+            FreeSummonUse::AnySummon => true,
+        }
+    }
+}
+
+/// Summons that don't cost any orbs. Free summons are used before tickets when both apply.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FreeSummons {
+    /// Summons given for free by the banner, e.g. the first summon on a New Heroes banner or
+    /// the FEH Pass daily free summons.
+    pub free: u32,
+    pub free_use: FreeSummonUse,
+    /// Summoning tickets.
+    pub tickets: u32,
+    pub ticket_use: FreeSummonUse,
+}
@@ -0,0 +1,510 @@
+//! Exact orbs-spent distributions for goals simple enough to enumerate every possible state.
+
+use std::collections::HashMap;
+
+use enumset::EnumSet;
+
+use crate::{
+    banner::{GenericBanner, PoolSpec},
+    cost::FreeSummons,
+    frequency_counter::FrequencyCounter,
+    goal::UnitCountGoal,
+    sim::{pool_rates, Status},
+    types::{Color, Pool, Rarity},
+};
+
+/// Probability mass of the trials still unfinished when the solver stops.
+const EPSILON: f64 = 1e-12;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct State {
+    status: Status,
+    copies: u32,
+}
+
+/// What a stone in a session can turn out to be, as far as the goal is concerned.
+#[derive(Copy, Clone, Debug)]
+enum Stone {
+    /// A stone of the goal's color from `pool`, which is the goal unit if `hit`.
+    GoalColor { pool: PoolSpec, hit: bool },
+    /// A stone of another color from `pool`.
+    OtherColor { pool: PoolSpec },
+}
+
+/// Where a session can lead: the index of the next state (or `None` if the goal was reached), the
+/// orbs spent on the session, and the chance of it happening.
+type Transition = (Option<usize>, u32, f64);
+
+struct Solver<'a> {
+    banner: &'a GenericBanner,
+    color: Color,
+    pools: EnumSet<Pool>,
+    min_rarity: Rarity,
+    state_indices: HashMap<State, usize>,
+    states: Vec<State>,
+    transitions: Vec<Option<Vec<Transition>>>,
+}
+
+/// Probabilities of the states reached with a particular number of orbs spent.
+#[derive(Default)]
+struct Level {
+    probs: Vec<f64>,
+    touched: Vec<usize>,
+}
+
+impl Level {
+    fn add(&mut self, index: usize, prob: f64) {
+        if index >= self.probs.len() {
+            self.probs.resize(index + 1, 0.0);
+        }
+        if self.probs[index] == 0.0 {
+            self.touched.push(index);
+        }
+        self.probs[index] += prob;
+    }
+
+    fn total(&self) -> f64 {
+        self.touched.iter().map(|&index| self.probs[index]).sum()
+    }
+}
+
+/// The exact distribution of orbs spent to reach `goal`, as the probability of each orb count.
+///
+/// Only goals for a single unit are supported, and the player is assumed to summon like the
+/// default [`SimConfig`](crate::sim::SimConfig) does: only the goal's color, with no free
+/// summons.
+pub fn solve_until_goal(banner: &GenericBanner, goal: &UnitCountGoal) -> Option<Vec<f64>> {
+    let [unit] = goal.units[..] else {
+        return None;
+    };
+    if unit.copies == 0 {
+        return Some(vec![1.0]);
+    }
+    // Sessions that cost nothing would never move the orbs spent forward
+    if banner.costs.cost(0) == 0 {
+        return None;
+    }
+    let mut solver = Solver {
+        banner,
+        color: unit.color,
+        pools: unit.pools,
+        min_rarity: unit.min_rarity,
+        state_indices: HashMap::new(),
+        states: Vec::new(),
+        transitions: Vec::new(),
+    };
+    // A unit that can't be pulled is only reached if the sparks can cover every copy, and a spark
+    // that doesn't repeat only covers one
+    let can_pull = solver
+        .stones(&Status::default())
+        .iter()
+        .any(|(stone, _)| matches!(stone, Stone::GoalColor { hit: true, .. }));
+    let sparks_cover_goal = banner.has_spark
+        && banner.rate_model.spark_threshold > 0
+        && (banner.rate_model.spark_repeats || unit.copies <= 1);
+    if !can_pull && !sparks_cover_goal {
+        return None;
+    }
+
+    // One more than the most orbs a session can cost, so that orb counts can be kept in a ring
+    // buffer
+    let ring_size = banner.costs.max_session_cost() as usize + 1;
+    let mut result = Vec::new();
+    let mut levels = (0..ring_size).map(|_| Level::default()).collect::<Vec<_>>();
+    let start = solver.index(State {
+        status: solver.normalize(Status::default()),
+        copies: unit.copies,
+    });
+    levels[0].add(start, 1.0);
+    for orbs in 0.. {
+        let mut level = std::mem::take(&mut levels[orbs % ring_size]);
+        for &index in &level.touched {
+            let prob = std::mem::take(&mut level.probs[index]);
+            for &(next, cost, next_prob) in solver.transitions(index) {
+                debug_assert!(cost > 0 && (cost as usize) < ring_size);
+                let prob = prob * next_prob;
+                let orbs = orbs + cost as usize;
+                match next {
+                    Some(next) => levels[orbs % ring_size].add(next, prob),
+                    None => {
+                        if result.len() <= orbs {
+                            result.resize(orbs + 1, 0.0);
+                        }
+                        result[orbs] += prob;
+                    }
+                }
+            }
+        }
+        level.touched.clear();
+        levels[orbs % ring_size] = level;
+        if levels.iter().map(Level::total).sum::<f64>() < EPSILON {
+            break;
+        }
+    }
+    Some(result)
+}
+
+/// Like [`solve_until_goal`], but scaled to `total` samples so that it can be used in place of
+/// the results of [`sim_until_goal_many`](crate::sim::sim_until_goal_many).
+pub fn solve_until_goal_counter(
+    banner: &GenericBanner,
+    goal: &UnitCountGoal,
+    total: u32,
+) -> Option<FrequencyCounter> {
+    let probabilities = solve_until_goal(banner, goal)?;
+    let mut counter = FrequencyCounter::new();
+    for (orbs, prob) in probabilities.into_iter().enumerate() {
+        let count = (prob * total as f64).round() as u32;
+        if count > 0 {
+            counter[orbs as u32] = count;
+        }
+    }
+    Some(counter)
+}
+
+impl Solver<'_> {
+    /// Merges states that can't be told apart by anything that happens later.
+    fn normalize(&self, mut status: Status) -> Status {
+        status.orbs_spent = 0;
+        let spark_threshold = self.banner.rate_model.spark_threshold;
+        if !self.banner.has_spark || spark_threshold == 0 {
+            status.total_pulled = 0;
+        } else if self.banner.rate_model.spark_repeats {
+            status.total_pulled %= spark_threshold;
+        } else {
+            status.total_pulled = status.total_pulled.min(spark_threshold);
+        }
+        if !self.banner.has_charges {
+            status.focus_charges = 0;
+        }
+        status
+    }
+
+    fn index(&mut self, state: State) -> usize {
+        *self.state_indices.entry(state).or_insert_with(|| {
+            self.states.push(state);
+            self.transitions.push(None);
+            self.states.len() - 1
+        })
+    }
+
+    fn transitions(&mut self, index: usize) -> &[Transition] {
+        if self.transitions[index].is_none() {
+            let transitions = self
+                .session_transitions(self.states[index])
+                .into_iter()
+                .map(|(next, orbs, prob)| (next.map(|next| self.index(next)), orbs, prob))
+                .collect();
+            self.transitions[index] = Some(transitions);
+        }
+        self.transitions[index].as_deref().unwrap()
+    }
+
+    fn stones(&self, status: &Status) -> Vec<(Stone, f64)> {
+        let pools = self.banner.pools();
+        let rates = pool_rates(
+            &pools,
+            &self.banner.rate_model,
+            self.banner.rate_model.pity_steps(status.pity_count),
+            status.focus_charge_active(self.banner),
+        );
+        let mut stones = Vec::new();
+        // The goal unit is the first unit of its color among the pools of each kind
+        let mut passed = EnumSet::<Pool>::new();
+        for (&pool, &rate) in pools.iter().zip(&rates) {
+            let size = pool.sizes[self.color as usize] as f64;
+            let total = pool.sizes.iter().map(|&size| size as f64).sum::<f64>();
+            let has_goal_unit = size > 0.0 && !passed.contains(pool.kind);
+            if size > 0.0 {
+                passed |= pool.kind;
+            }
+            if rate == 0.0 || total == 0.0 {
+                continue;
+            }
+            let color_rate = rate * size / total;
+            let hit_rate = if self.pools.contains(pool.kind) && has_goal_unit {
+                self.banner.rarity_chance(pool.kind, self.min_rarity) / size
+            } else {
+                0.0
+            };
+            stones.push((Stone::GoalColor { pool, hit: true }, color_rate * hit_rate));
+            stones.push((
+                Stone::GoalColor { pool, hit: false },
+                color_rate * (1.0 - hit_rate),
+            ));
+            stones.push((Stone::OtherColor { pool }, rate - color_rate));
+        }
+        stones.retain(|&(_, prob)| prob > 0.0);
+        stones
+    }
+
+    /// Every way a session starting from `start` can end: the state afterwards (or `None` if the
+    /// goal was reached), the orbs spent on the session, and the chance of it happening.
+    fn session_transitions(&self, start: State) -> Vec<(Option<State>, u32, f64)> {
+        let stones = self.stones(&start.status);
+        let start_sparks = self.banner.sparks_earned(start.status.total_pulled);
+        let free_summons = FreeSummons::default();
+        let mut finished = HashMap::<u32, f64>::new();
+        // (state, stones pulled, stopped early) -> probability
+        let mut states = HashMap::<(State, u32, bool), f64>::new();
+        states.insert((start, 0, false), 1.0);
+        for i in 0..5 {
+            let mut next_states = HashMap::new();
+            for ((state, num_pulled, stopped), prob) in states {
+                if stopped {
+                    *next_states.entry((state, num_pulled, true)).or_default() += prob;
+                    continue;
+                }
+                for &(stone, stone_prob) in &stones {
+                    let prob = prob * stone_prob;
+                    let (pool, hit) = match stone {
+                        Stone::GoalColor { pool, hit } => (pool, hit),
+                        Stone::OtherColor { pool } if num_pulled == 0 && i == 4 => (pool, false),
+                        Stone::OtherColor { .. } => {
+                            *next_states.entry((state, num_pulled, false)).or_default() += prob;
+                            continue;
+                        }
+                    };
+                    let payment = state.status.payment(self.banner, &free_summons, num_pulled);
+                    let mut next = state;
+                    next.status.update(&pool, payment, &self.banner.rate_model);
+                    if hit {
+                        next.copies -= 1;
+                        if next.copies == 0 {
+                            *finished.entry(next.status.orbs_spent).or_default() += prob;
+                            continue;
+                        }
+                    }
+                    // Don't finish the session if a spark is enough to reach the goal
+                    let stop = self.banner.sparks_earned(next.status.total_pulled) > start_sparks
+                        && next.copies == 1;
+                    *next_states.entry((next, num_pulled + 1, stop)).or_default() += prob;
+                }
+            }
+            states = next_states;
+        }
+
+        let mut transitions = finished
+            .into_iter()
+            .map(|(orbs, prob)| (None, orbs, prob))
+            .collect::<Vec<_>>();
+        let mut next_states = HashMap::<(State, u32), f64>::new();
+        for ((mut state, num_pulled, _), prob) in states {
+            let orbs = state.status.orbs_spent;
+            let sparks = self.banner.sparks_earned(state.status.total_pulled) - start_sparks;
+            if sparks >= state.copies {
+                transitions.push((None, orbs, prob));
+                continue;
+            }
+            state.copies -= sparks;
+            debug_assert!((1..=5).contains(&num_pulled));
+            state.status = self.normalize(state.status);
+            *next_states.entry((state, orbs)).or_default() += prob;
+        }
+        transitions.extend(
+            next_states
+                .into_iter()
+                .map(|((state, orbs), prob)| (Some(state), orbs, prob)),
+        );
+        transitions
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use enumset::EnumSet;
+
+    use crate::{
+        banner::{ChargeEffect, PityEffect, StandardBanner},
+        cost::OrbCosts,
+        goal::UnitGoal,
+        sim::{sim_until_goal_many, test_banner_and_goal, SimConfig},
+    };
+
+    use super::*;
+
+    fn percentile(probabilities: &[f64], p: f64) -> u32 {
+        let mut total = 0.0;
+        probabilities
+            .iter()
+            .position(|prob| {
+                total += prob;
+                total >= p
+            })
+            .unwrap() as u32
+    }
+
+    fn counter_percentile(counter: &FrequencyCounter, p: f64) -> u32 {
+        let total = counter.iter().sum::<u32>() as f64;
+        let probabilities = counter
+            .iter()
+            .map(|&count| count as f64 / total)
+            .collect::<Vec<_>>();
+        percentile(&probabilities, p)
+    }
+
+    /// The red unit of [`test_banner_and_goal`], which can also come from the 4★ focus pool.
+    fn goal(copies: u32) -> UnitCountGoal {
+        let (_, mut goal) = test_banner_and_goal();
+        goal.units[0].copies = copies;
+        goal.units[0].pools |= Pool::FourstarFocus;
+        goal
+    }
+
+    /// Banner of [`test_banner_and_goal`] with a spark.
+    fn spark_banner() -> GenericBanner {
+        let (mut banner, _) = test_banner_and_goal();
+        banner.has_spark = true;
+        banner
+    }
+
+    /// Solves `goal` exactly and checks that the probabilities add up to 1 and that each
+    /// percentile is within 5% plus `tolerance` orbs of a simulation's.
+    fn assert_matches_sim(
+        banner: &GenericBanner,
+        goal: &UnitCountGoal,
+        tolerance: u32,
+    ) -> Vec<f64> {
+        let exact = solve_until_goal(banner, goal).unwrap();
+        assert!((exact.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        let sim = sim_until_goal_many(banner, goal, &SimConfig::default(), 20000, 0);
+        for p in [0.25, 0.5, 0.75, 0.9] {
+            let (exact, sim) = dbg!(percentile(&exact, p), counter_percentile(&sim, p));
+            assert!(exact.abs_diff(sim) <= exact / 20 + tolerance);
+        }
+        exact
+    }
+
+    #[test]
+    fn test_exact_matches_sim() {
+        let banner = StandardBanner::NewHeroes {
+            focus: [2, 1, 1, 0],
+            fourstar_focus: [1, 0, 0, 0],
+        }
+        .as_generic_banner(false);
+        for copies in [1, 2] {
+            assert_matches_sim(&banner, &goal(copies), 5);
+        }
+    }
+
+    #[test]
+    fn test_exact_spark() {
+        let banner = spark_banner();
+        let exact = solve_until_goal(&banner, &goal(1)).unwrap();
+        // The spark guarantees the unit within 40 summons
+        assert!(exact.len() <= 201);
+        assert!((exact.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+
+        let counter = solve_until_goal_counter(&banner, &goal(1), 1_000_000).unwrap();
+        assert!(counter.iter().sum::<u32>().abs_diff(1_000_000) < 100);
+    }
+
+    #[test]
+    fn test_exact_repeating_spark() {
+        let mut banner = spark_banner();
+        banner.rate_model.spark_repeats = true;
+        banner.rate_model.spark_threshold = 20;
+        let exact = assert_matches_sim(&banner, &goal(2), 5);
+        // Two sparks within 40 summons, of at most 5 orbs each
+        assert!(exact.len() <= 201);
+    }
+
+    #[test]
+    fn test_exact_extra_pools() {
+        let banner = spark_banner();
+        let pool = PoolSpec {
+            kind: Pool::Focus,
+            rate: 3,
+            sizes: [1, 1, 0, 0],
+            pity: PityEffect::Reset,
+            charges: ChargeEffect::Spend,
+        };
+        // The goal unit in the first focus pool, then in the extra one
+        for focus_sizes in [[1, 1, 1, 1], [0, 1, 1, 1]] {
+            let banner = GenericBanner {
+                focus_sizes,
+                extra_pools: vec![pool],
+                ..banner.clone()
+            };
+            assert_matches_sim(&banner, &goal(1), 5);
+        }
+    }
+
+    #[test]
+    fn test_exact_costs() {
+        let mut banner = StandardBanner::NewHeroes {
+            focus: [2, 1, 1, 0],
+            fourstar_focus: [1, 0, 0, 0],
+        }
+        .as_generic_banner(false);
+        banner.costs = OrbCosts::flat(6);
+        assert_matches_sim(&banner, &goal(1), 6);
+
+        banner.costs = OrbCosts::default().with_first_summon(0);
+        assert_eq!(solve_until_goal(&banner, &goal(1)), None);
+    }
+
+    #[test]
+    fn test_exact_rarity() {
+        let banner = StandardBanner::NewHeroes {
+            focus: [2, 1, 1, 0],
+            fourstar_focus: [1, 0, 0, 0],
+        }
+        .as_generic_banner(false);
+        // Requiring a 5★ copy makes the 4★ focus pool useless
+        let mut fivestar_goal = goal(1);
+        fivestar_goal.units[0].min_rarity = Rarity::FiveStar;
+        let mut focus_goal = goal(1);
+        focus_goal.units[0].pools = EnumSet::from(Pool::Focus);
+        let fivestar = solve_until_goal(&banner, &fivestar_goal).unwrap();
+        let focus = solve_until_goal(&banner, &focus_goal).unwrap();
+        assert_eq!(fivestar.len(), focus.len());
+        for (a, b) in fivestar.iter().zip(focus.iter()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+        let any = solve_until_goal(&banner, &goal(1)).unwrap();
+        assert!(percentile(&any, 0.5) < percentile(&fivestar, 0.5));
+    }
+
+    #[test]
+    fn test_exact_unsupported() {
+        let (banner, _) = test_banner_and_goal();
+        let mut goal = goal(1);
+        goal.units.push(goal.units[0]);
+        assert_eq!(solve_until_goal(&banner, &goal), None);
+    }
+
+    #[test]
+    fn test_exact_unreachable() {
+        let (mut no_red, _) = test_banner_and_goal();
+        no_red.focus_sizes = [0, 1, 1, 1];
+        assert_eq!(solve_until_goal(&no_red, &goal(1)), None);
+        let mut fivestar_goal = UnitCountGoal::new(
+            vec![UnitGoal {
+                color: Color::Red,
+                copies: 1,
+                pools: EnumSet::from(Pool::FourstarFocus),
+                min_rarity: Rarity::FiveStar,
+            }],
+            true,
+        );
+        let mut fourstar_focus = StandardBanner::NewHeroes {
+            focus: [1, 1, 1, 1],
+            fourstar_focus: [1, 0, 0, 0],
+        }
+        .as_generic_banner(false);
+        fourstar_focus.has_spark = false;
+        assert_eq!(solve_until_goal(&fourstar_focus, &fivestar_goal), None);
+        // A spark still gets there
+        let mut with_spark = no_red.clone();
+        with_spark.has_spark = true;
+        assert!(solve_until_goal(&with_spark, &goal(1)).is_some());
+        // but one spark can't cover two copies
+        assert_eq!(solve_until_goal(&with_spark, &goal(2)), None);
+        fivestar_goal.units[0].copies = 0;
+        assert_eq!(
+            solve_until_goal(&fourstar_focus, &fivestar_goal),
+            Some(vec![1.0])
+        );
+    }
+}
@@ -27,6 +27,80 @@ impl FrequencyCounter {
             .zip(other.data.iter_mut())
             .for_each(|(a, b)| *a += *b);
     }
+
+    /// Total number of samples counted.
+    pub fn total(&self) -> u32 {
+        self.data.iter().sum()
+    }
+
+    /// The value of the `rank`th smallest sample, counting from 0.
+    pub fn value_at_rank(&self, rank: u32) -> Option<u32> {
+        let mut cum_total = 0;
+        self.data
+            .iter()
+            .position(|&count| {
+                cum_total += count;
+                cum_total > rank
+            })
+            .map(|value| value as u32)
+    }
+
+    /// The average of the samples.
+    pub fn mean(&self) -> Option<f64> {
+        let total = self.total();
+        if total == 0 {
+            return None;
+        }
+        let sum = self
+            .data
+            .iter()
+            .enumerate()
+            .map(|(value, &count)| value as f64 * count as f64)
+            .sum::<f64>();
+        Some(sum / total as f64)
+    }
+
+    /// Confidence interval for the true mean, given that these samples were drawn at random.
+    /// `z` is the standard score of the confidence level, e.g. 1.96 for 95%.
+    pub fn mean_interval(&self, z: f64) -> Option<(f64, f64)> {
+        let mean = self.mean()?;
+        let n = self.total() as f64;
+        let variance = self
+            .data
+            .iter()
+            .enumerate()
+            .map(|(value, &count)| count as f64 * (value as f64 - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1.0).max(1.0);
+        let spread = z * (variance / n).sqrt();
+        Some((mean - spread, mean + spread))
+    }
+
+    /// The value that a fraction `p` of the samples are less than or equal to.
+    pub fn percentile(&self, p: f64) -> Option<u32> {
+        let total = self.total();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((p * total as f64).ceil() as u32).clamp(1, total) - 1;
+        self.value_at_rank(rank)
+    }
+
+    /// Confidence interval for the true value of the `p` percentile, given that these samples
+    /// were drawn at random, with `z` as in [`Self::mean_interval`].
+    pub fn percentile_interval(&self, p: f64, z: f64) -> Option<(u32, u32)> {
+        let total = self.total();
+        if total == 0 {
+            return None;
+        }
+        // The number of samples below the true percentile is binomially distributed, so
+        // the bounds are the samples at ranks a few standard deviations to either side.
+        let n = total as f64;
+        let spread = z * (n * p * (1.0 - p)).sqrt();
+        let low = (n * p - spread).floor().max(0.0) as u32;
+        let high = ((n * p + spread).ceil() as u32).min(total - 1);
+        Some((self.value_at_rank(low)?, self.value_at_rank(high)?))
+    }
 }
 
 impl Index<u32> for FrequencyCounter {
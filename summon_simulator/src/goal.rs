@@ -1,7 +1,11 @@
 use enumset::EnumSet;
 use serde::{Deserialize, Serialize};
 
-use crate::types::{Color, Pool};
+use crate::{
+    banner::GenericBanner,
+    metrics::Metric,
+    types::{Color, Pool, Rarity},
+};
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Goal {
@@ -9,6 +13,16 @@ pub enum Goal {
     OrbBudget(BudgetGoal),
 }
 
+impl Goal {
+    /// The metric that answers the goal's question.
+    pub fn result_metric(&self) -> Metric {
+        match self {
+            Goal::Quantity(_) => Metric::OrbsSpent,
+            Goal::OrbBudget(_) => Metric::GoalCopies,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct UnitCountGoal {
     pub units: Vec<UnitGoal>,
@@ -21,6 +35,10 @@ pub struct UnitGoal {
     pub color: Color,
     pub copies: u32,
     pub pools: EnumSet<Pool>,
+    /// Copies pulled at a lower rarity don't count, e.g. 3★ copies when a 4★ one is needed to
+    /// inherit its skills.
+    #[serde(default)]
+    pub min_rarity: Rarity,
 }
 
 impl UnitCountGoal {
@@ -46,7 +64,8 @@ impl UnitCountGoal {
             .fold(EnumSet::new(), |set, unit| set | unit.color);
     }
 
-    pub fn pull(&mut self, pool: Pool, color: Color, unit_index: u8) {
+    /// Counts a pulled unit towards the goal, returning whether it was a unit that was needed.
+    pub fn pull(&mut self, pool: Pool, color: Color, unit_index: u8, rarity: Rarity) -> bool {
         for (idx, unit) in self
             .units
             .iter_mut()
@@ -54,11 +73,39 @@ impl UnitCountGoal {
             .enumerate()
         {
             if idx as u8 == unit_index {
+                if rarity < unit.min_rarity {
+                    return false;
+                }
                 unit.copies -= 1;
                 self.calculate_colors();
-                return;
+                return true;
             }
         }
+        false
+    }
+
+    /// Counts copies of each unit pulled before the simulation started, by the unit's index.
+    /// Returns how many of them were needed.
+    pub fn add_progress(&mut self, copies: &[u32]) -> u32 {
+        let mut needed = 0;
+        for (unit, &copies) in self.units.iter_mut().zip(copies) {
+            let pulled = copies.min(unit.copies);
+            unit.copies -= pulled;
+            needed += pulled;
+        }
+        self.calculate_colors();
+        needed
+    }
+
+    /// Counts a copy of the unit at `index` gained from a spark.
+    pub fn spark(&mut self, index: usize) {
+        self.units[index].copies -= 1;
+        self.calculate_colors();
+    }
+
+    /// Copies still needed of each unit.
+    pub fn copies(&self) -> Vec<u32> {
+        self.units.iter().map(|unit| unit.copies).collect()
     }
 
     pub fn finished(&self) -> bool {
@@ -77,9 +124,78 @@ pub enum BudgetGoalLimit {
     UntilSpark,
 }
 
+/// A unit whose copies are counted by a [`BudgetGoal`].
 #[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
-pub struct BudgetGoal {
+pub struct BudgetUnit {
     pub color: Color,
-    pub limit: BudgetGoalLimit,
     pub pools: EnumSet<Pool>,
+    #[serde(default)]
+    pub min_rarity: Rarity,
+}
+
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct BudgetGoal {
+    /// Units of the same color and pool are told apart by their order in this list.
+    pub units: Vec<BudgetUnit>,
+    pub limit: BudgetGoalLimit,
+}
+
+impl BudgetGoal {
+    /// A goal that counts every focus unit of `banner`, in order of color and then of position
+    /// within the color, so that the per-unit copies in [`crate::metrics::Metrics::unit_copies`]
+    /// show the haul for the whole banner. The first units of each color are the ones that are
+    /// also in the 4★ focus pool.
+    pub fn whole_banner(banner: &GenericBanner, limit: BudgetGoalLimit) -> Self {
+        // Counting by kind takes in any extra focus pools too
+        let focus_sizes = banner.pool_sizes(Pool::Focus);
+        let fourstar_focus_sizes = banner.pool_sizes(Pool::FourstarFocus);
+        let mut units = Vec::new();
+        for color in EnumSet::<Color>::all() {
+            let focus = focus_sizes[color as usize];
+            let fourstar_focus = fourstar_focus_sizes[color as usize];
+            for i in 0..focus {
+                let pools = if i < fourstar_focus {
+                    Pool::Focus | Pool::FourstarFocus
+                } else {
+                    EnumSet::from(Pool::Focus)
+                };
+                units.push(BudgetUnit {
+                    color,
+                    pools,
+                    min_rarity: Rarity::ThreeStar,
+                });
+            }
+        }
+        Self { units, limit }
+    }
+
+    pub fn colors(&self) -> EnumSet<Color> {
+        self.units
+            .iter()
+            .fold(EnumSet::new(), |set, unit| set | unit.color)
+    }
+
+    /// Whether any of the units can be pulled from `pool` in `color`.
+    pub fn wants(&self, pool: Pool, color: Color) -> bool {
+        self.units
+            .iter()
+            .any(|unit| unit.color == color && unit.pools.contains(pool))
+    }
+
+    /// The index of the goal unit that a pulled unit is, if it's one of them and rare enough.
+    pub fn unit_at(
+        &self,
+        pool: Pool,
+        color: Color,
+        unit_index: u8,
+        rarity: Rarity,
+    ) -> Option<usize> {
+        self.units
+            .iter()
+            .enumerate()
+            .filter(|(_, unit)| unit.color == color && unit.pools.contains(pool))
+            .nth(unit_index as usize)
+            .filter(|(_, unit)| rarity >= unit.min_rarity)
+            .map(|(idx, _)| idx)
+    }
 }
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+
+use crate::frequency_counter::FrequencyCounter;
+
+/// How many orbs the player has to spend over time. Day 0 is today, and income arrives at the
+/// start of each following day.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrbIncome {
+    pub starting_orbs: u32,
+    pub daily: u32,
+    /// Orbs gained once a week, on day `weekly_day` and every 7th day after it.
+    pub weekly: u32,
+    pub weekly_day: u32,
+    /// One-off grants from events, as (day, orbs).
+    pub grants: Vec<(u32, u32)>,
+}
+
+/// How long it takes until the orbs for a goal are available.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeToGoal {
+    /// Number of trials that needed each number of days.
+    pub days: FrequencyCounter,
+    /// Number of trials that needed more orbs than the income ever provides.
+    pub never: u32,
+}
+
+impl OrbIncome {
+    /// Orbs gained on `day` alone.
+    pub fn income_on_day(&self, day: u32) -> u32 {
+        let mut orbs = self
+            .grants
+            .iter()
+            .filter(|&&(grant_day, _)| grant_day == day)
+            .map(|&(_, orbs)| orbs)
+            .sum::<u32>();
+        if day == 0 {
+            orbs += self.starting_orbs;
+        } else {
+            orbs += self.daily;
+            if day >= self.weekly_day && (day - self.weekly_day).is_multiple_of(7) {
+                orbs += self.weekly;
+            }
+        }
+        orbs
+    }
+
+    /// Total orbs available by `day`.
+    pub fn orbs_by_day(&self, day: u32) -> u32 {
+        (0..=day).map(|day| self.income_on_day(day)).sum()
+    }
+
+    fn last_grant_day(&self) -> u32 {
+        self.grants.iter().map(|&(day, _)| day).max().unwrap_or(0)
+    }
+
+    /// The first day by which at least `orbs` are available, or `None` if that never happens.
+    pub fn days_until(&self, orbs: u32) -> Option<u32> {
+        let mut total = 0;
+        for day in 0.. {
+            total += self.income_on_day(day);
+            if total >= orbs {
+                return Some(day);
+            }
+            if day >= self.last_grant_day() && self.daily == 0 && self.weekly == 0 {
+                return None;
+            }
+        }
+        unreachable!()
+    }
+
+    /// Turns a distribution of orbs spent, like the results of a quantity goal, into a
+    /// distribution of the days it takes to save up for the goal.
+    pub fn time_to_goal(&self, orbs_spent: &FrequencyCounter) -> TimeToGoal {
+        let mut result = TimeToGoal::default();
+        let mut day = 0;
+        let mut total = self.income_on_day(0);
+        for (orbs, &count) in orbs_spent.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            while total < orbs as u32 {
+                if day >= self.last_grant_day() && self.daily == 0 && self.weekly == 0 {
+                    result.never += count;
+                    break;
+                }
+                day += 1;
+                total += self.income_on_day(day);
+            }
+            if total >= orbs as u32 {
+                result.days[day] += count;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_income() {
+        let income = OrbIncome {
+            starting_orbs: 50,
+            daily: 2,
+            weekly: 10,
+            weekly_day: 3,
+            grants: vec![(5, 20)],
+        };
+        assert_eq!(income.orbs_by_day(0), 50);
+        assert_eq!(income.orbs_by_day(3), 50 + 3 * 2 + 10);
+        assert_eq!(income.orbs_by_day(10), 50 + 10 * 2 + 2 * 10 + 20);
+        assert_eq!(income.days_until(50), Some(0));
+        assert_eq!(income.days_until(51), Some(1));
+        assert_eq!(income.days_until(66), Some(3));
+
+        let mut orbs_spent = FrequencyCounter::new();
+        orbs_spent[40] += 3;
+        orbs_spent[66] += 2;
+        orbs_spent[67] += 1;
+        let time = income.time_to_goal(&orbs_spent);
+        assert_eq!(time.days[0], 3);
+        assert_eq!(time.days[3], 2);
+        assert_eq!(time.days[4], 1);
+        assert_eq!(time.never, 0);
+
+        let no_income = OrbIncome {
+            starting_orbs: 50,
+            grants: vec![(2, 10)],
+            ..Default::default()
+        };
+        assert_eq!(no_income.days_until(60), Some(2));
+        assert_eq!(no_income.days_until(61), None);
+        assert_eq!(no_income.time_to_goal(&orbs_spent).never, 3);
+    }
+}
@@ -1,6 +1,16 @@
 pub mod banner;
+pub mod campaign;
+pub mod cost;
+pub mod exact;
 pub mod frequency_counter;
 pub mod goal;
+pub mod income;
+pub mod metrics;
+pub mod observer;
+pub mod optimize;
 pub mod sim;
+pub mod strategy;
+pub mod stream;
+pub mod trace;
 pub mod types;
 mod weightedindex;
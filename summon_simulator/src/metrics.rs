@@ -0,0 +1,195 @@
+use enumset::{EnumSet, EnumSetType};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    banner::RateModel,
+    frequency_counter::FrequencyCounter,
+    sim::Status,
+    types::{Pool, Rarity},
+};
+
+/// Something measured in every trial.
+#[derive(Hash, Debug, PartialOrd, Ord, EnumSetType, Serialize, Deserialize)]
+pub enum Metric {
+    OrbsSpent,
+    Summons,
+    /// Copies of goal units pulled, including ones from sparks.
+    GoalCopies,
+    /// Copies of goal units pulled at 5★, including ones from sparks.
+    FivestarGoalCopies,
+    /// Copies of goal units pulled at 4★, e.g. from the 4★ focus pool.
+    FourstarGoalCopies,
+    ThreestarGoalCopies,
+    OffFocusFivestars,
+    FourstarSpecials,
+    /// Units pulled from the common pool, whether or not they were goal units.
+    Commons,
+    SparksUsed,
+    /// The highest pity rate reached, in steps of pity.
+    MaxPity,
+}
+
+impl Metric {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::OrbsSpent => "Orbs spent",
+            Metric::Summons => "Summons",
+            Metric::GoalCopies => "Goal copies",
+            Metric::FivestarGoalCopies => "Goal copies at 5★",
+            Metric::FourstarGoalCopies => "Goal copies at 4★",
+            Metric::ThreestarGoalCopies => "Goal copies at 3★",
+            Metric::OffFocusFivestars => "Off-focus 5★s",
+            Metric::FourstarSpecials => "4★ specials",
+            Metric::Commons => "Common pool units",
+            Metric::SparksUsed => "Sparks used",
+            Metric::MaxPity => "Highest pity",
+        }
+    }
+}
+
+/// The metrics of a single trial.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrialMetrics {
+    pub orbs_spent: u32,
+    pub summons: u32,
+    pub goal_copies: u32,
+    pub fivestar_goal_copies: u32,
+    pub fourstar_goal_copies: u32,
+    pub threestar_goal_copies: u32,
+    pub off_focus_fivestars: u32,
+    pub fourstar_specials: u32,
+    pub commons: u32,
+    pub sparks_used: u32,
+    pub max_pity: u32,
+    /// Index of the goal unit each spark was used on.
+    pub sparked_units: Vec<usize>,
+    /// Copies pulled of each goal unit, including ones from sparks.
+    pub unit_copies: Vec<u32>,
+}
+
+impl TrialMetrics {
+    pub fn get(&self, metric: Metric) -> u32 {
+        match metric {
+            Metric::OrbsSpent => self.orbs_spent,
+            Metric::Summons => self.summons,
+            Metric::GoalCopies => self.goal_copies,
+            Metric::FivestarGoalCopies => self.fivestar_goal_copies,
+            Metric::FourstarGoalCopies => self.fourstar_goal_copies,
+            Metric::ThreestarGoalCopies => self.threestar_goal_copies,
+            Metric::OffFocusFivestars => self.off_focus_fivestars,
+            Metric::FourstarSpecials => self.fourstar_specials,
+            Metric::Commons => self.commons,
+            Metric::SparksUsed => self.sparks_used,
+            Metric::MaxPity => self.max_pity,
+        }
+    }
+
+    pub(crate) fn pulled(&mut self, pool: Pool) {
+        match pool {
+            Pool::Fivestar => self.off_focus_fivestars += 1,
+            Pool::FourstarSpecial => self.fourstar_specials += 1,
+            Pool::Common => self.commons += 1,
+            _ => {}
+        }
+    }
+
+    pub(crate) fn spark_used(&mut self, unit: usize) {
+        self.sparks_used += 1;
+        self.sparked_units.push(unit);
+        self.goal_copy_pulled(Rarity::FiveStar);
+    }
+
+    /// Counts a goal copy by its rarity. [`TrialMetrics::goal_copies`] is counted separately,
+    /// since it also includes copies from before the start.
+    pub(crate) fn goal_copy_pulled(&mut self, rarity: Rarity) {
+        match rarity {
+            Rarity::FiveStar => self.fivestar_goal_copies += 1,
+            Rarity::FourStar => self.fourstar_goal_copies += 1,
+            Rarity::ThreeStar => self.threestar_goal_copies += 1,
+        }
+    }
+
+    pub(crate) fn session_start(&mut self, status: &Status, rate_model: &RateModel) {
+        self.max_pity = self.max_pity.max(rate_model.pity_steps(status.pity_count));
+    }
+
+    pub(crate) fn finish(&mut self, status: &Status) {
+        self.orbs_spent = status.orbs_spent;
+        self.summons = status.total_pulled;
+    }
+}
+
+/// The distribution of every [`Metric`] over many trials.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metrics {
+    counters: Vec<FrequencyCounter>,
+    /// Number of sparks used on each goal unit, by index.
+    sparked_units: FrequencyCounter,
+    /// Distribution of the copies pulled of each goal unit.
+    unit_copies: Vec<FrequencyCounter>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            counters: vec![FrequencyCounter::new(); EnumSet::<Metric>::variant_count() as usize],
+            sparked_units: FrequencyCounter::new(),
+            unit_copies: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, trial: &TrialMetrics) {
+        for metric in EnumSet::<Metric>::all() {
+            self.counters[metric as usize][trial.get(metric)] += 1;
+        }
+        for &unit in &trial.sparked_units {
+            self.sparked_units[unit as u32] += 1;
+        }
+        if self.unit_copies.len() < trial.unit_copies.len() {
+            self.unit_copies
+                .resize(trial.unit_copies.len(), FrequencyCounter::new());
+        }
+        for (counter, &copies) in self.unit_copies.iter_mut().zip(&trial.unit_copies) {
+            counter[copies] += 1;
+        }
+    }
+
+    pub fn get(&self, metric: Metric) -> &FrequencyCounter {
+        &self.counters[metric as usize]
+    }
+
+    /// How many sparks were used on each goal unit over all trials, by the unit's index in the
+    /// goal.
+    pub fn sparked_units(&self) -> &FrequencyCounter {
+        &self.sparked_units
+    }
+
+    /// The distribution of copies pulled of each goal unit, by the unit's index in the goal.
+    pub fn unit_copies(&self) -> &[FrequencyCounter] {
+        &self.unit_copies
+    }
+
+    pub fn into_counter(mut self, metric: Metric) -> FrequencyCounter {
+        self.counters.swap_remove(metric as usize)
+    }
+
+    pub fn combine(&mut self, other: Metrics) {
+        for (counter, other) in self.counters.iter_mut().zip(other.counters) {
+            counter.combine(other);
+        }
+        self.sparked_units.combine(other.sparked_units);
+        if self.unit_copies.len() < other.unit_copies.len() {
+            self.unit_copies
+                .resize(other.unit_copies.len(), FrequencyCounter::new());
+        }
+        for (counter, other) in self.unit_copies.iter_mut().zip(other.unit_copies) {
+            counter.combine(other);
+        }
+    }
+}
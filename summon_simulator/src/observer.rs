@@ -0,0 +1,41 @@
+use crate::{
+    metrics::TrialMetrics,
+    sim::Status,
+    types::{Color, Pool},
+};
+
+/// Receives the events of each trial as it's simulated, e.g. to measure something that
+/// [`crate::metrics::Metrics`] doesn't. Every method does nothing by default, and `()` ignores
+/// every event at no cost.
+pub trait Observer {
+    /// A new session was drawn, before any of its stones were pulled.
+    fn session_start(&mut self, _stones: &[(Pool, Color); 5], _status: &Status) {}
+
+    /// The stone at `index` in the session was pulled. `status` is after paying for it.
+    fn pulled(&mut self, _index: usize, _stone: (Pool, Color), _status: &Status) {}
+
+    /// A stone from a pool that resets or reduces pity was pulled, e.g. a focus or off-focus
+    /// 5★, and pity went down.
+    fn pity_reset(&mut self, _pity_before: u32, _status: &Status) {}
+
+    /// An off-focus 5★ added a focus charge.
+    fn focus_charge_gained(&mut self, _status: &Status) {}
+
+    /// A focus unit was pulled with focus charges active, using them up.
+    fn focus_charges_spent(&mut self, _status: &Status) {}
+
+    /// A spark was used on the goal unit at `unit`.
+    fn spark_used(&mut self, _unit: usize) {}
+
+    /// The goal of a quantity goal was reached. Orb budget goals have nothing to finish.
+    fn goal_finished(&mut self, _status: &Status) {}
+
+    /// The session is over. `goal_copies` gives the copies still needed of each goal unit for
+    /// quantity goals, or the copies pulled so far for orb budget goals.
+    fn session_end(&mut self, _status: &Status, _goal_copies: impl FnOnce() -> Vec<u32>) {}
+
+    /// The trial is over, with these results.
+    fn trial_end(&mut self, _metrics: &TrialMetrics) {}
+}
+
+impl Observer for () {}
@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use enumset::EnumSet;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    banner::GenericBanner,
+    frequency_counter::FrequencyCounter,
+    goal::{Goal, UnitCountGoal},
+    sim::{Sim, SimConfig},
+    strategy::{PullStrategy, SessionState},
+    types::Color,
+};
+
+/// A color-selection rule from the family that [`Optimizer`] searches. Stones of the goal's
+/// colors are always pulled, and the rule decides which others are pulled too. The default only
+/// pulls the goal's colors.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ColorRule {
+    /// Also pull stones of this color, e.g. to build up pity or focus charges.
+    pub extra_color: Option<Color>,
+    /// Stop pulling the extra color once pity reaches this many steps.
+    pub extra_until_pity: u32,
+    /// Pull every stone once the spark is at most this many summons away.
+    pub all_near_spark: Option<u32>,
+}
+
+impl PullStrategy for ColorRule {
+    fn should_pull(&self, state: &SessionState, index: usize) -> bool {
+        let color = state.session[index].1;
+        if state.goal_colors.contains(color) {
+            return true;
+        }
+        let rate_model = &state.banner.rate_model;
+        if self.extra_color == Some(color)
+            && rate_model.pity_steps(state.status.pity_count) < self.extra_until_pity
+        {
+            return true;
+        }
+        match self.all_near_spark {
+            Some(distance) => {
+                let threshold = rate_model.spark_threshold;
+                // A repeating spark counts again from each one earned
+                let pulled = if rate_model.spark_repeats && threshold > 0 {
+                    state.status.total_pulled % threshold
+                } else {
+                    state.status.total_pulled
+                };
+                state.banner.has_spark && pulled < threshold && threshold - pulled <= distance
+            }
+            None => false,
+        }
+    }
+}
+
+impl ColorRule {
+    /// Every rule worth trying for `goal` on `banner`: each color outside the goal as the extra
+    /// color up to a range of pity steps, each combined with pulling everything at a range of
+    /// distances from the spark if the banner has one.
+    pub fn family(banner: &GenericBanner, goal: &UnitCountGoal) -> Vec<ColorRule> {
+        let max_pity = banner.rate_model.max_pity_steps;
+        let mut extras = vec![(None, 0)];
+        for color in EnumSet::<Color>::all() - goal.colors() {
+            for until_pity in [2, 4, 8, 12, 16]
+                .into_iter()
+                .filter(|&steps| steps < max_pity)
+                .chain([u32::MAX])
+            {
+                extras.push((Some(color), until_pity));
+            }
+        }
+        let near_spark = if banner.has_spark {
+            vec![None, Some(5), Some(10), Some(20)]
+        } else {
+            vec![None]
+        };
+        extras
+            .into_iter()
+            .flat_map(|(extra_color, extra_until_pity)| {
+                near_spark.iter().map(move |&all_near_spark| ColorRule {
+                    extra_color,
+                    extra_until_pity,
+                    all_near_spark,
+                })
+            })
+            .collect()
+    }
+}
+
+/// What the optimizer minimizes about the orbs spent.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Objective {
+    Mean,
+    Median,
+    /// The orbs spent by this fraction of trials, e.g. 0.9 for a rule that's cheap in bad luck.
+    Percentile(f64),
+}
+
+impl Objective {
+    /// The objective's value for `data` and its confidence interval.
+    fn estimate(&self, data: &FrequencyCounter, z: f64) -> Option<(f64, (f64, f64))> {
+        let p = match *self {
+            Objective::Mean => return Some((data.mean()?, data.mean_interval(z)?)),
+            Objective::Median => 0.5,
+            Objective::Percentile(p) => p,
+        };
+        let (low, high) = data.percentile_interval(p, z)?;
+        Some((data.percentile(p)? as f64, (low as f64, high as f64)))
+    }
+}
+
+/// How one rule did.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RuleResult {
+    pub rule: ColorRule,
+    /// The objective's value over the simulated trials.
+    pub estimate: f64,
+    /// Confidence interval for the objective's true value.
+    pub interval: (f64, f64),
+}
+
+/// Searches a family of [`ColorRule`]s for the cheapest way to reach a goal.
+#[derive(Clone, Debug)]
+pub struct Optimizer {
+    pub banner: GenericBanner,
+    pub goal: UnitCountGoal,
+    /// Everything but the strategy, which is replaced by each rule in turn.
+    pub config: SimConfig,
+    pub rules: Vec<ColorRule>,
+    pub objective: Objective,
+    /// Trials to simulate for each rule.
+    pub iters: u32,
+    /// Confidence level of the intervals, as the `z` of [`FrequencyCounter::mean_interval`].
+    pub z: f64,
+}
+
+impl Optimizer {
+    /// Tries every rule of [`ColorRule::family`] for 10000 trials each, minimizing the mean.
+    pub fn new(banner: GenericBanner, goal: UnitCountGoal) -> Self {
+        let rules = ColorRule::family(&banner, &goal);
+        Self {
+            banner,
+            goal,
+            config: SimConfig::default(),
+            rules,
+            objective: Objective::Mean,
+            iters: 10000,
+            z: 1.96,
+        }
+    }
+
+    /// Simulates every rule and returns their results, best first. Every rule gets the same
+    /// seed, so differences between them come from the rules rather than from luck.
+    pub fn run(&self, seed: u64) -> Vec<RuleResult> {
+        let mut results = self
+            .rules
+            .iter()
+            .filter_map(|&rule| {
+                let config = SimConfig {
+                    strategy: Arc::new(rule),
+                    ..self.config.clone()
+                };
+                let data = Sim::new(self.banner.clone(), Goal::Quantity(self.goal.clone()))
+                    .with_seed(seed)
+                    .with_config(config)
+                    .sim(self.iters)
+                    .data()
+                    .clone();
+                let (estimate, interval) = self.objective.estimate(&data, self.z)?;
+                Some(RuleResult {
+                    rule,
+                    estimate,
+                    interval,
+                })
+            })
+            .collect::<Vec<_>>();
+        results.sort_by(|a, b| a.estimate.total_cmp(&b.estimate));
+        results
+    }
+
+    /// The rule with the best result.
+    pub fn best(&self, seed: u64) -> Option<RuleResult> {
+        self.run(seed).into_iter().next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        sim::{test_banner_and_goal, Status},
+        strategy::{ColorStrategy, GoalProgress},
+        types::Pool,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_optimizer() {
+        let (mut banner, goal) = test_banner_and_goal();
+        banner.has_spark = true;
+        // 3 extra colors with 6 pity limits each, plus no extra color, times 4 spark distances
+        assert_eq!(ColorRule::family(&banner, &goal).len(), (3 * 6 + 1) * 4);
+
+        // The default rule is the same as only pulling the goal's colors
+        let data = |strategy: Arc<dyn PullStrategy>| {
+            Sim::new(banner.clone(), Goal::Quantity(goal.clone()))
+                .with_seed(1)
+                .with_config(SimConfig {
+                    strategy,
+                    ..Default::default()
+                })
+                .sim(1000)
+                .data()
+                .clone()
+        };
+        assert_eq!(
+            data(Arc::new(ColorRule::default())),
+            data(Arc::new(ColorStrategy::GoalColors))
+        );
+
+        let always_blue = ColorRule {
+            extra_color: Some(Color::Blue),
+            extra_until_pity: u32::MAX,
+            all_near_spark: None,
+        };
+        let mut optimizer = Optimizer::new(banner, goal);
+        optimizer.rules = vec![always_blue, ColorRule::default()];
+        optimizer.iters = 10000;
+        for objective in [
+            Objective::Mean,
+            Objective::Median,
+            Objective::Percentile(0.9),
+        ] {
+            optimizer.objective = objective;
+            let results = optimizer.run(0);
+            assert_eq!(results.len(), 2);
+            assert!(results[0].estimate <= results[1].estimate);
+            for result in &results {
+                assert!(result.interval.0 <= result.estimate);
+                assert!(result.estimate <= result.interval.1);
+            }
+        }
+        // Pulling another color costs orbs without getting closer to the goal, except through
+        // the occasional pity reset it doesn't need
+        optimizer.objective = Objective::Mean;
+        assert_eq!(optimizer.best(0).unwrap().rule, ColorRule::default());
+    }
+
+    #[test]
+    fn test_near_repeating_spark() {
+        let (mut banner, goal) = test_banner_and_goal();
+        banner.has_spark = true;
+        banner.rate_model.spark_repeats = true;
+        let rule = ColorRule {
+            all_near_spark: Some(5),
+            ..Default::default()
+        };
+        let pulls_blue = |total_pulled| {
+            let status = Status {
+                total_pulled,
+                ..Default::default()
+            };
+            rule.should_pull(
+                &SessionState {
+                    banner: &banner,
+                    session: &[(Pool::Common, Color::Blue); 5],
+                    num_pulled: 0,
+                    status: &status,
+                    goal_colors: goal.colors(),
+                    goal: GoalProgress::Quantity(&goal),
+                },
+                0,
+            )
+        };
+        assert!(pulls_blue(36));
+        assert!(!pulls_blue(45));
+        // Close to the second spark
+        assert!(pulls_blue(76));
+    }
+}
@@ -1,173 +1,647 @@
+use std::sync::Arc;
+
 use rand::{prelude::Distribution, Rng, SeedableRng};
 use rand_xoshiro::Xoshiro128Plus;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    banner::GenericBanner,
+    banner::{ChargeEffect, GenericBanner, PityEffect, PoolSpec, RateModel},
+    cost::FreeSummons,
     frequency_counter::FrequencyCounter,
-    goal::{BudgetGoal, BudgetGoalLimit, Goal, UnitCountGoal},
-    types::{Color, Pool},
+    goal::{BudgetGoal, BudgetGoalLimit, Goal, UnitCountGoal, UnitGoal},
+    metrics::{Metric, Metrics, TrialMetrics},
+    observer::Observer,
+    strategy::{ColorStrategy, GoalProgress, PullStrategy, SessionState, SparkPolicy, StopPolicy},
+    trace::{TraceRecorder, TrialTrace},
+    types::{Color, Pool, Rarity},
     weightedindex::{WeightedIndexColor, WeightedIndexPool},
 };
 
-#[derive(Copy, Clone, Debug)]
-struct Status {
-    total_pulled: u32,
-    orbs_spent: u32,
-    pity_count: u32,
-    focus_charges: u32,
+/// The player's progress on the banner during a single trial.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Status {
+    pub total_pulled: u32,
+    /// Orbs actually paid, not counting free summons and tickets.
+    pub orbs_spent: u32,
+    pub pity_count: u32,
+    pub focus_charges: u32,
+    pub free_summons_used: u32,
+    pub tickets_used: u32,
+}
+
+/// Where every trial starts, for a player who has already summoned on the banner. The results
+/// include what happened before the start, so e.g. orbs already spent count towards the orbs
+/// spent. Leave `status.orbs_spent` at 0 to only count the orbs still to be spent.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StartState {
+    pub status: Status,
+    /// Copies of each goal unit already pulled, by the unit's index in the goal.
+    pub goal_copies: Vec<u32>,
+    /// Sparks already earned but not used yet.
+    pub sparks_available: u32,
+}
+
+/// How the player summons, as opposed to what the banner offers.
+#[derive(Clone, Debug)]
+pub struct SimConfig {
+    pub strategy: Arc<dyn PullStrategy>,
+    pub free_summons: FreeSummons,
+    pub spark: SparkPolicy,
+    pub stop: StopPolicy,
+    pub start: StartState,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            strategy: Arc::new(ColorStrategy::default()),
+            free_summons: FreeSummons::default(),
+            spark: SparkPolicy::default(),
+            stop: StopPolicy::default(),
+            start: StartState::default(),
+        }
+    }
+}
+
+/// How a single summon gets paid for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Payment {
+    Free,
+    Ticket,
+    Orbs(u32),
+}
+
+impl Payment {
+    /// Orbs that this summon costs.
+    pub(crate) fn orbs(self) -> u32 {
+        match self {
+            Payment::Orbs(cost) => cost,
+            Payment::Free | Payment::Ticket => 0,
+        }
+    }
 }
 
-struct DistributionCache {
+pub(crate) struct DistributionCache {
+    pools: Vec<PoolSpec>,
+    /// Number of the first unit of each color in each pool, counting the units of the earlier
+    /// pools of the same kind.
+    unit_offsets: Vec<[u8; 4]>,
     color_dists: Vec<WeightedIndexColor>,
     pool_dists: Vec<WeightedIndexPool>,
+    common_threestar_share: f64,
 }
 
 impl DistributionCache {
     pub fn new(banner: &GenericBanner) -> Self {
+        let rate_model = &banner.rate_model;
+        let pools = banner.pools();
         let mut pool_dists = Vec::new();
-        for i in 0..=24 {
-            pool_dists.push(get_pool_dist(banner.starting_rates(), i, true));
-        }
-        for i in 0..=24 {
-            pool_dists.push(get_pool_dist(banner.starting_rates(), i, false));
-        }
-        let color_dists = vec![
-            get_color_dist(banner.pool_sizes(Pool::Focus)),
-            get_color_dist(banner.pool_sizes(Pool::Fivestar)),
-            get_color_dist(banner.pool_sizes(Pool::FourstarFocus)),
-            get_color_dist(banner.pool_sizes(Pool::FourstarSpecial)),
-            get_color_dist(banner.pool_sizes(Pool::Common)),
-        ];
+        for i in 0..=rate_model.max_pity_steps {
+            pool_dists.push(get_pool_dist(&pools, rate_model, i, true));
+        }
+        for i in 0..=rate_model.max_pity_steps {
+            pool_dists.push(get_pool_dist(&pools, rate_model, i, false));
+        }
+        let color_dists = pools
+            .iter()
+            .map(|spec| get_color_dist(spec.sizes))
+            .collect();
+        let unit_offsets = pools
+            .iter()
+            .enumerate()
+            .map(|(i, spec)| {
+                let mut offsets = [0; 4];
+                for earlier in pools[..i]
+                    .iter()
+                    .filter(|earlier| earlier.kind == spec.kind)
+                {
+                    for (offset, size) in offsets.iter_mut().zip(earlier.sizes) {
+                        *offset += size;
+                    }
+                }
+                offsets
+            })
+            .collect();
         Self {
+            pools,
+            unit_offsets,
             color_dists,
             pool_dists,
+            common_threestar_share: banner.common_threestar_share(),
         }
     }
 
     pub fn get_pool_dist(&self, pity_incr: u32, focus_charge_active: bool) -> &WeightedIndexPool {
-        let pity_incr = pity_incr.min(24);
+        // The first half of the list has focus charges active, the second half doesn't
+        let num_steps = self.pool_dists.len() / 2;
+        let pity_incr = (pity_incr as usize).min(num_steps - 1);
         if focus_charge_active {
-            &self.pool_dists[pity_incr as usize]
+            &self.pool_dists[pity_incr]
         } else {
-            &self.pool_dists[pity_incr as usize + 25]
+            &self.pool_dists[pity_incr + num_steps]
         }
     }
 
-    pub fn get_color_dist(&self, pool: Pool) -> &WeightedIndexColor {
-        &self.color_dists[pool as usize]
+    pub fn get_color_dist(&self, pool: usize) -> &WeightedIndexColor {
+        &self.color_dists[pool]
+    }
+
+    /// The banner's pool with index `pool`.
+    pub fn pool(&self, pool: usize) -> &PoolSpec {
+        &self.pools[pool]
+    }
+
+    /// Picks which unit of its kind a stone of `color` from the pool with index `pool` is.
+    pub fn unit_index(&self, pool: usize, color: Color, rng: &mut impl Rng) -> u8 {
+        self.unit_offsets[pool][color as usize]
+            + rng.gen_range(0..self.pools[pool].sizes[color as usize])
+    }
+
+    /// Picks the rarity of a unit pulled from `pool`. Only common units need a roll.
+    pub fn pull_rarity(&self, pool: Pool, rng: &mut impl Rng) -> Rarity {
+        pool.fixed_rarity().unwrap_or_else(|| {
+            if rng.gen_bool(self.common_threestar_share) {
+                Rarity::ThreeStar
+            } else {
+                Rarity::FourStar
+            }
+        })
     }
 }
 
 impl Status {
-    fn update(&mut self, pool: Pool, session_orb_count: u32) {
+    /// How the next summon would be paid for, with `num_pulled` stones already pulled this
+    /// session.
+    pub(crate) fn payment(
+        &self,
+        banner: &GenericBanner,
+        free_summons: &FreeSummons,
+        num_pulled: u32,
+    ) -> Payment {
+        if self.free_summons_used < free_summons.free && free_summons.free_use.allows(num_pulled) {
+            Payment::Free
+        } else if self.tickets_used < free_summons.tickets
+            && free_summons.ticket_use.allows(num_pulled)
+        {
+            Payment::Ticket
+        } else {
+            Payment::Orbs(banner.costs.cost(num_pulled))
+        }
+    }
+
+    /// Orbs that the next summon costs, with `num_pulled` stones already pulled this session.
+    fn next_cost(
+        &self,
+        banner: &GenericBanner,
+        free_summons: &FreeSummons,
+        num_pulled: u32,
+    ) -> u32 {
+        self.payment(banner, free_summons, num_pulled).orbs()
+    }
+
+    /// Records a summon from `pool`, paid for with `payment`.
+    pub(crate) fn update(&mut self, pool: &PoolSpec, payment: Payment, rate_model: &RateModel) {
+        match payment {
+            Payment::Free => self.free_summons_used += 1,
+            Payment::Ticket => self.tickets_used += 1,
+            Payment::Orbs(cost) => self.orbs_spent += cost,
+        }
         self.total_pulled += 1;
-        self.orbs_spent += match session_orb_count {
-            1 => 5,
-            2..=4 => 4,
-            5 => 3,
-            _ => panic!("Invalid num_pulled"),
-        };
 
-        // Pity rate: reset for a focus, subtract 2% worth for off-focus, increment otherwise
-        self.pity_count = match pool {
-            Pool::Focus => 0,
-            Pool::Fivestar => self.pity_count.saturating_sub(20),
-            _ => self.pity_count + 1,
+        // Pity rate: reset for a focus, subtract some for off-focus, increment otherwise
+        self.pity_count = match pool.pity {
+            PityEffect::Reset => 0,
+            PityEffect::Reduce => self
+                .pity_count
+                .saturating_sub(rate_model.off_focus_pity_reset),
+            PityEffect::Increase => self.pity_count + 1,
         };
 
         // Focus charges: reset for a focus unit while charges are active, increment for off-focus
-        self.focus_charges = match (pool, self.focus_charges) {
-            (Pool::Fivestar, _) => (self.focus_charges + 1).min(3),
-            (Pool::Focus, 3) => 0,
+        self.focus_charges = match pool.charges {
+            ChargeEffect::Gain => (self.focus_charges + 1).min(rate_model.charge_threshold),
+            ChargeEffect::Spend if self.focus_charges == rate_model.charge_threshold => 0,
             _ => self.focus_charges,
         };
     }
+
+    /// Whether focus charges turn every 5★ into a focus unit on the next session.
+    pub(crate) fn focus_charge_active(&self, banner: &GenericBanner) -> bool {
+        banner.has_charges && self.focus_charges >= banner.rate_model.charge_threshold
+    }
+}
+
+/// Decides when a simulation's results are precise enough to stop.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoppingRule {
+    /// Percentiles of the results to check, as fractions.
+    pub percentiles: Vec<f64>,
+    /// The widest acceptable confidence interval for each percentile, in orbs or copies.
+    pub max_width: u32,
+    /// Confidence level, as the `z` of [`FrequencyCounter::percentile_interval`].
+    pub z: f64,
+    /// Don't stop before this many iterations, in case an unlucky start looks very precise.
+    pub min_iters: u32,
+    /// Stop after this many iterations no matter how imprecise the results are.
+    pub max_iters: u32,
+}
+
+impl Default for StoppingRule {
+    fn default() -> Self {
+        Self {
+            percentiles: vec![0.25, 0.5, 0.75, 0.9, 0.99],
+            max_width: 5,
+            z: 1.96,
+            min_iters: 1000,
+            max_iters: 10_000_000,
+        }
+    }
+}
+
+impl StoppingRule {
+    /// Confidence intervals of each of the percentiles.
+    pub fn intervals(&self, data: &FrequencyCounter) -> Vec<(u32, u32)> {
+        self.percentiles
+            .iter()
+            .map(|&p| data.percentile_interval(p, self.z).unwrap_or((0, u32::MAX)))
+            .collect()
+    }
+
+    pub fn is_satisfied(&self, data: &FrequencyCounter) -> bool {
+        let total = data.total();
+        total >= self.max_iters
+            || (total >= self.min_iters
+                && self
+                    .intervals(data)
+                    .iter()
+                    .all(|&(low, high)| high - low <= self.max_width))
+    }
 }
 
 pub struct Sim {
     banner: GenericBanner,
     goal: Goal,
-    data: FrequencyCounter,
+    config: SimConfig,
+    metrics: Metrics,
+    seed: u64,
+    num_chunks: u64,
 }
 
 impl Sim {
+    /// Creates a simulation with a randomly-chosen seed.
     pub fn new(banner: GenericBanner, goal: Goal) -> Self {
         Self {
             banner,
             goal,
-            data: FrequencyCounter::new(),
+            config: SimConfig::default(),
+            metrics: Metrics::new(),
+            seed: rand::thread_rng().gen(),
+            num_chunks: 0,
         }
     }
 
+    /// Replaces the seed, so that the same sequence of calls to `sim` always produces the same
+    /// results.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.num_chunks = 0;
+        self
+    }
+
+    pub fn with_config(mut self, config: SimConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Starts every trial from `start` instead of from a fresh banner.
+    pub fn with_start(mut self, start: StartState) -> Self {
+        self.config.start = start;
+        self
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn next_chunk_seed(&mut self) -> u64 {
+        let seed = stream_seed(self.seed, self.num_chunks);
+        self.num_chunks += 1;
+        seed
+    }
+
+    /// Runs `iters` more trials. Each call draws from its own sub-stream of the seed.
+    ///
+    /// With the `parallel` feature, the trials are spread over all cores. The results for a seed
+    /// are the same either way.
     pub fn sim(&mut self, iters: u32) -> &mut Self {
-        let new_data = match &self.goal {
-            Goal::Quantity(goal) => sim_until_goal_many(&self.banner, goal, iters),
-            Goal::OrbBudget(goal) => sim_orb_budget_many(&self.banner, goal, iters),
+        let seed = self.next_chunk_seed();
+        let sim_block = |(block_iters, block_seed)| match &self.goal {
+            Goal::Quantity(goal) => {
+                sim_until_goal_metrics(&self.banner, goal, &self.config, block_iters, block_seed)
+            }
+            Goal::OrbBudget(goal) => {
+                sim_orb_budget_metrics(&self.banner, goal, &self.config, block_iters, block_seed)
+            }
         };
+        let combine = |mut metrics: Metrics, other| {
+            metrics.combine(other);
+            metrics
+        };
+
+        #[cfg(feature = "parallel")]
+        let new_metrics = {
+            use rayon::prelude::*;
+            blocks(iters, seed)
+                .collect::<Vec<_>>()
+                .into_par_iter()
+                .map(sim_block)
+                .reduce(Metrics::new, combine)
+        };
+        #[cfg(not(feature = "parallel"))]
+        let new_metrics = blocks(iters, seed)
+            .map(sim_block)
+            .fold(Metrics::new(), combine);
+
+        self.metrics.combine(new_metrics);
+        self
+    }
+
+    /// Runs trials in chunks of `chunk_size` until `rule` is satisfied.
+    pub fn sim_until_precise(&mut self, rule: &StoppingRule, chunk_size: u32) -> &mut Self {
+        while !rule.is_satisfied(self.data()) {
+            let remaining = rule.max_iters.saturating_sub(self.data().total());
+            self.sim(chunk_size.min(remaining).max(1));
+        }
+        self
+    }
+
+    /// Confidence intervals of the rule's percentiles with the results so far.
+    pub fn percentile_intervals(&self, rule: &StoppingRule) -> Vec<(u32, u32)> {
+        rule.intervals(self.data())
+    }
 
-        self.data.combine(new_data);
+    /// Runs `iters` more trials like [`Sim::sim`], telling `observer` about every event along
+    /// the way. The trials are the same ones `sim` would have run, but they're always run on a
+    /// single thread.
+    pub fn sim_observed(&mut self, iters: u32, observer: &mut impl Observer) -> &mut Self {
+        let seed = self.next_chunk_seed();
+        let cache = DistributionCache::new(&self.banner);
+        for (block_iters, seed) in blocks(iters, seed) {
+            let mut rng = Xoshiro128Plus::seed_from_u64(seed);
+            for _ in 0..block_iters {
+                let trial_metrics = self.sim_trial(&mut rng, &cache, observer);
+                self.metrics.record(&trial_metrics);
+            }
+        }
         self
     }
 
+    /// Runs `iters` more trials like [`Sim::sim`], and also returns the full history of each
+    /// trial that `keep` accepts. The trials are the same ones `sim` would have run, but recording
+    /// them makes this much slower, so it's meant for debugging.
+    pub fn sim_traced(
+        &mut self,
+        iters: u32,
+        mut keep: impl FnMut(&TrialTrace) -> bool,
+    ) -> Vec<TrialTrace> {
+        let seed = self.next_chunk_seed();
+        let cache = DistributionCache::new(&self.banner);
+        let mut traces = Vec::new();
+        for (block_iters, seed) in blocks(iters, seed) {
+            let mut rng = Xoshiro128Plus::seed_from_u64(seed);
+            for trial in 0..block_iters {
+                let mut recorder = TraceRecorder::default();
+                let trial_metrics = self.sim_trial(&mut rng, &cache, &mut recorder);
+                self.metrics.record(&trial_metrics);
+                let trace = TrialTrace {
+                    seed,
+                    trial,
+                    start_sparked_units: recorder.start_sparked_units,
+                    sessions: recorder.sessions,
+                    result: trial_metrics.get(self.goal.result_metric()),
+                };
+                if keep(&trace) {
+                    traces.push(trace);
+                }
+            }
+        }
+        traces
+    }
+
+    fn sim_trial(
+        &self,
+        rng: &mut impl Rng,
+        cache: &DistributionCache,
+        observer: &mut impl Observer,
+    ) -> TrialMetrics {
+        match &self.goal {
+            Goal::Quantity(goal) => sim_until_goal(
+                &self.banner,
+                &mut goal.clone(),
+                u32::MAX,
+                &self.config,
+                rng,
+                cache,
+                observer,
+            ),
+            Goal::OrbBudget(goal) => {
+                sim_orb_budget(&self.banner, goal, &self.config, rng, cache, observer)
+            }
+        }
+    }
+
+    /// The distribution of the goal's main result: orbs spent for quantity goals, copies pulled
+    /// for orb budget goals.
     pub fn data(&self) -> &FrequencyCounter {
-        &self.data
+        self.metrics.get(self.goal.result_metric())
     }
 
     pub fn into_data(self) -> FrequencyCounter {
-        self.data
+        self.metrics.into_counter(self.goal.result_metric())
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
     }
 }
 
+/// Trials are run in blocks of this many, each with its own sub-stream of the seed, so that
+/// the blocks can be simulated on separate threads without changing the results.
+const BLOCK_SIZE: u32 = 1000;
+
+/// Splits `iters` trials into blocks, as (number of trials, seed) pairs.
+fn blocks(iters: u32, seed: u64) -> impl Iterator<Item = (u32, u64)> {
+    (0..iters.div_ceil(BLOCK_SIZE)).map(move |block| {
+        (
+            (iters - block * BLOCK_SIZE).min(BLOCK_SIZE),
+            stream_seed(seed, block as u64),
+        )
+    })
+}
+
+/// Derives the seed of the `stream`th sub-stream of `seed`. Generators seeded from different
+/// streams are independent of each other, so chunks of work can be simulated separately and
+/// still be reproduced exactly.
+pub fn stream_seed(seed: u64, stream: u64) -> u64 {
+    // SplitMix64
+    let mut z = seed.wrapping_add(stream.wrapping_add(1).wrapping_mul(0x9E3779B97F4A7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
 pub fn sim_until_goal_many(
     banner: &GenericBanner,
     goal: &UnitCountGoal,
+    config: &SimConfig,
     iters: u32,
+    seed: u64,
 ) -> FrequencyCounter {
-    let mut counter = FrequencyCounter::new();
+    sim_until_goal_metrics(banner, goal, config, iters, seed).into_counter(Metric::OrbsSpent)
+}
+
+pub fn sim_until_goal_metrics(
+    banner: &GenericBanner,
+    goal: &UnitCountGoal,
+    config: &SimConfig,
+    iters: u32,
+    seed: u64,
+) -> Metrics {
+    let mut metrics = Metrics::new();
     let cache = DistributionCache::new(banner);
-    let mut rng = Xoshiro128Plus::from_rng(&mut rand::thread_rng()).unwrap();
+    let mut rng = Xoshiro128Plus::seed_from_u64(seed);
     for _ in 0..iters {
-        let result = sim_until_goal(banner, goal.clone(), &mut rng, &cache);
-        counter[result] += 1;
+        let result = sim_until_goal(
+            banner,
+            &mut goal.clone(),
+            u32::MAX,
+            config,
+            &mut rng,
+            &cache,
+            &mut (),
+        );
+        metrics.record(&result);
     }
-    counter
+    metrics
 }
 
-fn sim_until_goal(
+/// Simulates one trial, pulling until `goal` is finished or the next summon would take the orbs
+/// spent past `orb_limit`.
+pub(crate) fn sim_until_goal(
     banner: &GenericBanner,
-    mut goal: UnitCountGoal,
+    goal: &mut UnitCountGoal,
+    orb_limit: u32,
+    config: &SimConfig,
     rng: &mut impl Rng,
     cache: &DistributionCache,
-) -> u32 {
-    let mut status = Status {
-        total_pulled: 0,
-        orbs_spent: 0,
-        pity_count: 0,
-        focus_charges: 0,
-    };
+    observer: &mut impl Observer,
+) -> TrialMetrics {
+    let mut status = config.start.status;
+    let mut metrics = TrialMetrics::default();
+    let copies_needed = goal.copies();
+    metrics.goal_copies = goal.add_progress(&config.start.goal_copies);
     let has_common_unit = goal
         .units
         .iter()
         .any(|unit| unit.pools.contains(Pool::Common));
+    let mut sparks_available = config.start.sparks_available;
+    use_sparks(
+        &config.spark,
+        banner,
+        goal,
+        &status,
+        &mut sparks_available,
+        &mut metrics,
+        observer,
+    );
     'sim: loop {
+        if goal.finished()
+            || status.orbs_spent + status.next_cost(banner, &config.free_summons, 0) > orb_limit
+        {
+            break 'sim;
+        }
         let mut num_pulled = 0;
-        let session = make_session(banner, &status, rng, cache);
+        let mut out_of_orbs = false;
+        let session_start_orbs = status.orbs_spent;
+        let (session, sources) = make_session(banner, &status, rng, cache);
+        observer.session_start(&session, &status);
+        metrics.session_start(&status, &banner.rate_model);
         for (i, &(pool, color)) in session.iter().enumerate() {
-            if goal.colors().contains(color) || (num_pulled == 0 && i == 4) {
+            let state = SessionState {
+                banner,
+                session: &session,
+                num_pulled,
+                status: &status,
+                goal_colors: goal.colors(),
+                goal: GoalProgress::Quantity(goal),
+            };
+            // Once the goal is met, only a player who finishes every session keeps pulling
+            let finishing_session = goal.finished();
+            if finishing_session
+                || config.strategy.should_pull(&state, i)
+                || (num_pulled == 0 && i == 4)
+            {
+                let payment = status.payment(banner, &config.free_summons, num_pulled);
+                let next_cost = payment.orbs();
+                if num_pulled > 0
+                    && !config
+                        .stop
+                        .allows_cost(status.orbs_spent - session_start_orbs, next_cost)
+                {
+                    break;
+                }
+                if status.orbs_spent + next_cost > orb_limit {
+                    // The summons already made this session can still earn a spark
+                    out_of_orbs = true;
+                    break;
+                }
                 num_pulled += 1;
-                status.update(pool, num_pulled);
+                let status_before = status;
+                let source = sources[i];
+                let spec = cache.pool(source);
+                status.update(spec, payment, &banner.rate_model);
+                observe_pull(
+                    observer,
+                    banner,
+                    i,
+                    (pool, color),
+                    spec,
+                    &status_before,
+                    &status,
+                );
+                metrics.pulled(pool);
 
                 if has_common_unit || pool != Pool::Common {
-                    let unit_index = rng.gen_range(0..banner.pool_sizes(pool)[color as usize]);
-                    goal.pull(pool, color, unit_index);
-                    if goal.finished() {
+                    let unit_index = cache.unit_index(source, color, rng);
+                    let rarity = cache.pull_rarity(pool, rng);
+                    if goal.pull(pool, color, unit_index, rarity) {
+                        metrics.goal_copies += 1;
+                        metrics.goal_copy_pulled(rarity);
+                        if !finishing_session && goal.finished() {
+                            observer.goal_finished(&status);
+                        }
+                    }
+                    if goal.finished() && config.stop != StopPolicy::FinishSession {
+                        observer.session_end(&status, || goal.copies());
                         break 'sim;
                     }
                 }
 
+                if config.stop.ends_session(
+                    pool,
+                    status_before.pity_count,
+                    &status,
+                    &banner.rate_model,
+                ) {
+                    break;
+                }
+
                 // Don't finish the session if a spark is enough to reach the goal
+                let session_sparks = banner.sparks_earned(status.total_pulled)
+                    - banner.sparks_earned(status.total_pulled - num_pulled);
                 if banner.has_spark
-                    && status.total_pulled == 40
+                    && config.stop != StopPolicy::FinishSession
+                    && session_sparks + sparks_available > 0
                     && goal.units.iter().map(|unit| unit.copies).sum::<u32>() == 1
                 {
                     break;
@@ -176,114 +650,296 @@ fn sim_until_goal(
         }
         debug_assert!((1..=5).contains(&num_pulled));
         // Spark, if possible
-        if banner.has_spark && status.total_pulled >= 40 && (status.total_pulled - num_pulled) < 40
-        {
-            // If there's one unit with more copies required left than the others, pick that one
-            let max_copies_needed = goal.units.iter().map(|unit| unit.copies).max().unwrap();
-            let mut spark_candidates = goal
-                .units
-                .iter_mut()
-                .filter(|unit| unit.copies == max_copies_needed)
-                .collect::<Vec<_>>();
-            // If there are multiples, then just pick the first one
-            spark_candidates[0].copies -= 1;
-            if goal.finished() {
-                break 'sim;
-            }
+        sparks_available += banner.sparks_earned(status.total_pulled)
+            - banner.sparks_earned(status.total_pulled - num_pulled);
+        use_sparks(
+            &config.spark,
+            banner,
+            goal,
+            &status,
+            &mut sparks_available,
+            &mut metrics,
+            observer,
+        );
+        observer.session_end(&status, || goal.copies());
+        if goal.finished() || out_of_orbs {
+            break 'sim;
         }
     }
 
-    status.orbs_spent
+    // Sparks that were being saved still get used once the orbs run out
+    use_sparks(
+        &SparkPolicy::MostCopiesNeeded,
+        banner,
+        goal,
+        &status,
+        &mut sparks_available,
+        &mut metrics,
+        observer,
+    );
+
+    metrics.unit_copies = copies_needed
+        .iter()
+        .zip(goal.copies())
+        .map(|(needed, left)| needed - left)
+        .collect();
+    metrics.finish(&status);
+    observer.trial_end(&metrics);
+    metrics
+}
+
+/// Uses as many of the available sparks as `policy` wants to.
+fn use_sparks(
+    policy: &SparkPolicy,
+    banner: &GenericBanner,
+    goal: &mut UnitCountGoal,
+    status: &Status,
+    sparks_available: &mut u32,
+    metrics: &mut TrialMetrics,
+    observer: &mut impl Observer,
+) {
+    while *sparks_available > 0 && !goal.finished() {
+        let Some(idx) = policy.choose(banner, goal) else {
+            break;
+        };
+        *sparks_available -= 1;
+        goal.spark(idx);
+        metrics.goal_copies += 1;
+        metrics.spark_used(idx);
+        observer.spark_used(idx);
+        if goal.finished() {
+            observer.goal_finished(status);
+        }
+    }
 }
 
 pub fn sim_orb_budget_many(
     banner: &GenericBanner,
     goal: &BudgetGoal,
+    config: &SimConfig,
     iters: u32,
+    seed: u64,
 ) -> FrequencyCounter {
-    let mut counter = FrequencyCounter::new();
+    sim_orb_budget_metrics(banner, goal, config, iters, seed).into_counter(Metric::GoalCopies)
+}
+
+pub fn sim_orb_budget_metrics(
+    banner: &GenericBanner,
+    goal: &BudgetGoal,
+    config: &SimConfig,
+    iters: u32,
+    seed: u64,
+) -> Metrics {
+    let mut metrics = Metrics::new();
     let cache = DistributionCache::new(banner);
-    let mut rng = Xoshiro128Plus::from_rng(&mut rand::thread_rng()).unwrap();
+    let mut rng = Xoshiro128Plus::seed_from_u64(seed);
     for _ in 0..iters {
-        let result = sim_orb_budget(banner, goal, &mut rng, &cache);
-        counter[result] += 1;
+        let result = sim_orb_budget(banner, goal, config, &mut rng, &cache, &mut ());
+        metrics.record(&result);
     }
-    counter
+    metrics
 }
 
 fn sim_orb_budget(
     banner: &GenericBanner,
     goal: &BudgetGoal,
+    config: &SimConfig,
     rng: &mut impl Rng,
     cache: &DistributionCache,
-) -> u32 {
-    let mut status = Status {
-        total_pulled: 0,
-        orbs_spent: 0,
-        pity_count: 0,
-        focus_charges: 0,
-    };
-    let mut num_goal_units_pulled = 0;
-    let is_common_unit = goal.pools.contains(Pool::Common);
+    observer: &mut impl Observer,
+) -> TrialMetrics {
+    let mut status = config.start.status;
+    let mut metrics = TrialMetrics::default();
+    let goal_colors = goal.colors();
+    let mut copies = config.start.goal_copies.clone();
+    copies.resize(goal.units.len(), 0);
+    let mut sparks_saved = 0;
+    let spark_threshold = banner.rate_model.spark_threshold;
+    let is_common_unit = goal
+        .units
+        .iter()
+        .any(|unit| unit.pools.contains(Pool::Common));
+    for _ in 0..config.start.sparks_available {
+        if config.spark == SparkPolicy::SaveUntilEnd {
+            sparks_saved += 1;
+        } else {
+            let idx = budget_spark_target(&config.spark, banner, goal, &copies);
+            copies[idx] += 1;
+            metrics.spark_used(idx);
+            observer.spark_used(idx);
+        }
+    }
     loop {
+        let done = match goal.limit {
+            BudgetGoalLimit::OrbCount(limit) => {
+                status.orbs_spent + status.next_cost(banner, &config.free_summons, 0) > limit
+            }
+            // A repeating spark can always be earned again
+            BudgetGoalLimit::UntilSpark => {
+                !(banner.has_spark && banner.rate_model.spark_repeats && spark_threshold > 0)
+                    && status.total_pulled >= spark_threshold
+            }
+        };
+        if done {
+            break;
+        }
         let mut num_pulled = 0;
-        let session = make_session(banner, &status, rng, cache);
+        let session_start_orbs = status.orbs_spent;
+        let (session, sources) = make_session(banner, &status, rng, cache);
+        observer.session_start(&session, &status);
+        metrics.session_start(&status, &banner.rate_model);
         for (i, &(pool, color)) in session.iter().enumerate() {
-            let next_orb_cost = match num_pulled {
-                0 => 5,
-                1..=3 => 4,
-                4 => 3,
-                _ => panic!("Invalid num_pulled"),
-            };
+            let payment = status.payment(banner, &config.free_summons, num_pulled);
+            let next_orb_cost = payment.orbs();
             if let BudgetGoalLimit::OrbCount(limit) = goal.limit {
                 if status.orbs_spent + next_orb_cost > limit {
                     break;
                 }
             }
-            if goal.color == color || (num_pulled == 0 && i == 4) {
+            let state = SessionState {
+                banner,
+                session: &session,
+                num_pulled,
+                status: &status,
+                goal_colors,
+                goal: GoalProgress::OrbBudget {
+                    goal,
+                    copies: &copies,
+                },
+            };
+            if config.strategy.should_pull(&state, i) || (num_pulled == 0 && i == 4) {
+                if num_pulled > 0
+                    && !config
+                        .stop
+                        .allows_cost(status.orbs_spent - session_start_orbs, next_orb_cost)
+                {
+                    break;
+                }
                 num_pulled += 1;
-                status.update(pool, num_pulled);
+                let status_before = status;
+                let source = sources[i];
+                let spec = cache.pool(source);
+                status.update(spec, payment, &banner.rate_model);
+                observe_pull(
+                    observer,
+                    banner,
+                    i,
+                    (pool, color),
+                    spec,
+                    &status_before,
+                    &status,
+                );
+                metrics.pulled(pool);
 
-                if (is_common_unit || pool != Pool::Common)
-                    && goal.color == color
-                    && goal.pools.contains(pool)
-                {
-                    let unit_index = rng.gen_range(0..banner.pool_sizes(pool)[color as usize]);
-                    if unit_index == 0 {
-                        num_goal_units_pulled += 1;
+                if (is_common_unit || pool != Pool::Common) && goal.wants(pool, color) {
+                    let unit_index = cache.unit_index(source, color, rng);
+                    let rarity = cache.pull_rarity(pool, rng);
+                    if let Some(idx) = goal.unit_at(pool, color, unit_index, rarity) {
+                        copies[idx] += 1;
+                        metrics.goal_copy_pulled(rarity);
                     }
                 }
+
+                if config.stop.ends_session(
+                    pool,
+                    status_before.pity_count,
+                    &status,
+                    &banner.rate_model,
+                ) {
+                    break;
+                }
             }
         }
         debug_assert!((1..=5).contains(&num_pulled));
         // Spark, if possible
-        if banner.has_spark && status.total_pulled >= 40 && (status.total_pulled - num_pulled) < 40
-        {
-            num_goal_units_pulled += 1;
-            if goal.limit == BudgetGoalLimit::UntilSpark {
-                break;
+        let sparks = banner.sparks_earned(status.total_pulled)
+            - banner.sparks_earned(status.total_pulled - num_pulled);
+        for _ in 0..sparks {
+            if config.spark == SparkPolicy::SaveUntilEnd {
+                sparks_saved += 1;
+            } else {
+                let idx = budget_spark_target(&config.spark, banner, goal, &copies);
+                copies[idx] += 1;
+                metrics.spark_used(idx);
+                observer.spark_used(idx);
             }
         }
-
-        if let BudgetGoalLimit::OrbCount(limit) = goal.limit {
-            if status.orbs_spent + 5 > limit {
-                break;
-            }
+        observer.session_end(&status, || copies.clone());
+        if sparks > 0 && goal.limit == BudgetGoalLimit::UntilSpark {
+            break;
         }
     }
 
-    num_goal_units_pulled
+    for _ in 0..sparks_saved {
+        let idx = budget_spark_target(&SparkPolicy::MostCopiesNeeded, banner, goal, &copies);
+        copies[idx] += 1;
+        metrics.spark_used(idx);
+        observer.spark_used(idx);
+    }
+
+    metrics.goal_copies = copies.iter().sum();
+    metrics.unit_copies = copies;
+    metrics.finish(&status);
+    observer.trial_end(&metrics);
+    metrics
+}
+
+/// Picks the unit of a budget goal to spark. Every unit is still wanted, so "most copies
+/// needed" means the unit with the fewest copies so far.
+fn budget_spark_target(
+    policy: &SparkPolicy,
+    banner: &GenericBanner,
+    goal: &BudgetGoal,
+    copies: &[u32],
+) -> usize {
+    let most_copies = copies.iter().copied().max().unwrap_or(0);
+    let units = goal
+        .units
+        .iter()
+        .zip(copies)
+        .map(|(unit, &copies)| UnitGoal {
+            color: unit.color,
+            copies: most_copies + 1 - copies,
+            pools: unit.pools,
+            min_rarity: Rarity::ThreeStar,
+        })
+        .collect::<Vec<_>>();
+    policy.choose_from(banner, &units, false).unwrap_or(0)
+}
+
+/// Tells `observer` about a pulled stone and what it did to pity and focus charges.
+fn observe_pull(
+    observer: &mut impl Observer,
+    banner: &GenericBanner,
+    index: usize,
+    stone: (Pool, Color),
+    spec: &PoolSpec,
+    before: &Status,
+    after: &Status,
+) {
+    observer.pulled(index, stone, after);
+    if spec.pity != PityEffect::Increase && after.pity_count < before.pity_count {
+        observer.pity_reset(before.pity_count, after);
+    }
+    if banner.has_charges {
+        if after.focus_charges > before.focus_charges {
+            observer.focus_charge_gained(after);
+        } else if after.focus_charges < before.focus_charges {
+            observer.focus_charges_spent(after);
+        }
+    }
 }
 
+/// Draws the stones of a new session, along with the index of the pool each one came from.
 fn make_session(
     banner: &GenericBanner,
     status: &Status,
     rng: &mut impl Rng,
     cache: &DistributionCache,
-) -> [(Pool, Color); 5] {
+) -> ([(Pool, Color); 5], [usize; 5]) {
     let pool_dist = cache.get_pool_dist(
-        status.pity_count / 5,
-        banner.has_charges && status.focus_charges >= 3,
+        banner.rate_model.pity_steps(status.pity_count),
+        status.focus_charge_active(banner),
     );
 
     let mut gen = || {
@@ -293,7 +949,11 @@ fn make_session(
 
         (pool, color)
     };
-    [gen(), gen(), gen(), gen(), gen()]
+    let sources = [gen(), gen(), gen(), gen(), gen()];
+    (
+        sources.map(|(pool, color)| (cache.pool(pool).kind, color)),
+        sources.map(|(pool, _)| pool),
+    )
 }
 
 fn get_color_dist(pool_sizes: [u8; 4]) -> WeightedIndexColor {
@@ -301,47 +961,105 @@ fn get_color_dist(pool_sizes: [u8; 4]) -> WeightedIndexColor {
 }
 
 fn get_pool_dist(
-    starting_rates: [u8; 5],
+    pools: &[PoolSpec],
+    rate_model: &RateModel,
     pity_incr: u32,
     focus_charge_active: bool,
 ) -> WeightedIndexPool {
-    let pity_pct = pity_incr as f32 * 0.005;
-    let mut rates: [f32; 5] = std::array::from_fn(|i| starting_rates[i] as f32 / 100.0);
-    let fivestar_total = rates[Pool::Focus as usize] + rates[Pool::Fivestar as usize];
-    if pity_incr >= 24 {
-        rates[Pool::Focus as usize] /= fivestar_total;
-        rates[Pool::Fivestar as usize] /= fivestar_total;
-        rates[Pool::FourstarFocus as usize] = 0.0;
-        rates[Pool::FourstarSpecial as usize] = 0.0;
-        rates[Pool::Common as usize] = 0.0;
-    } else {
-        rates[Pool::Focus as usize] += pity_pct * rates[Pool::Focus as usize] / fivestar_total;
-        rates[Pool::Fivestar as usize] +=
-            pity_pct * rates[Pool::Fivestar as usize] / fivestar_total;
-        rates[Pool::FourstarFocus as usize] -=
-            pity_pct * rates[Pool::FourstarFocus as usize] / (1.0 - fivestar_total);
-        rates[Pool::FourstarSpecial as usize] -=
-            pity_pct * rates[Pool::FourstarSpecial as usize] / (1.0 - fivestar_total);
-        rates[Pool::Common as usize] -=
-            pity_pct * rates[Pool::Common as usize] / (1.0 - fivestar_total);
+    WeightedIndexPool::new(
+        pool_rates(pools, rate_model, pity_incr, focus_charge_active)
+            .into_iter()
+            .map(|rate| rate as f32),
+    )
+}
+
+/// The chance of a stone coming from each pool, after adjusting the starting rates for pity and
+/// focus charges. Pity moves rate from the other pools to the 5★ pools in proportion to their
+/// rates, and active focus charges move the rate of pools that earn charges to the pools that
+/// spend them.
+pub(crate) fn pool_rates(
+    pools: &[PoolSpec],
+    rate_model: &RateModel,
+    pity_incr: u32,
+    focus_charge_active: bool,
+) -> Vec<f64> {
+    let pity_pct = pity_incr as f64 * rate_model.pity_step_increase as f64 / 10000.0;
+    let mut rates: Vec<f64> = pools.iter().map(|spec| spec.rate as f64 / 100.0).collect();
+    let fivestar_total = pools
+        .iter()
+        .zip(&rates)
+        .filter(|(spec, _)| spec.is_fivestar())
+        .map(|(_, rate)| rate)
+        .sum::<f64>();
+    for (spec, rate) in pools.iter().zip(rates.iter_mut()) {
+        *rate = match (spec.is_fivestar(), pity_incr >= rate_model.max_pity_steps) {
+            (true, true) => *rate / fivestar_total,
+            (false, true) => 0.0,
+            (true, false) => *rate + pity_pct * *rate / fivestar_total,
+            (false, false) => *rate - pity_pct * *rate / (1.0 - fivestar_total),
+        };
     }
 
     if focus_charge_active {
-        rates[Pool::Focus as usize] += rates[Pool::Fivestar as usize];
-        rates[Pool::Fivestar as usize] = 0.0;
+        let charged = |effect| {
+            pools
+                .iter()
+                .zip(&rates)
+                .filter(|(spec, _)| spec.charges == effect)
+                .map(|(_, rate)| rate)
+                .sum::<f64>()
+        };
+        let (gained, spent) = (charged(ChargeEffect::Gain), charged(ChargeEffect::Spend));
+        if spent > 0.0 {
+            for (spec, rate) in pools.iter().zip(rates.iter_mut()) {
+                match spec.charges {
+                    ChargeEffect::Gain => *rate = 0.0,
+                    ChargeEffect::Spend => *rate += gained * *rate / spent,
+                    ChargeEffect::None => {}
+                }
+            }
+        }
     }
 
-    debug_assert!((rates.iter().sum::<f32>() - 1.0).abs() < 0.0000001);
+    debug_assert!((rates.iter().sum::<f64>() - 1.0).abs() < 0.0000001);
 
-    WeightedIndexPool::new(rates)
+    rates
 }
 
+/// The Standard banner with one focus unit of each color, and a goal of one copy of the red one,
+/// which most tests start from.
 #[cfg(test)]
-mod test {
+pub(crate) fn test_banner_and_goal() -> (GenericBanner, UnitCountGoal) {
     use enumset::EnumSet;
 
     use crate::{banner::StandardBanner, goal::UnitGoal};
 
+    let banner = StandardBanner::Standard {
+        focus: [1, 1, 1, 1],
+    }
+    .as_generic_banner(false);
+    let goal = UnitCountGoal::new(
+        vec![UnitGoal {
+            color: Color::Red,
+            copies: 1,
+            pools: EnumSet::from(Pool::Focus),
+            min_rarity: Rarity::ThreeStar,
+        }],
+        true,
+    );
+    (banner, goal)
+}
+
+#[cfg(test)]
+mod test {
+    use enumset::EnumSet;
+
+    use crate::{
+        banner::{BannerError, StandardBanner},
+        cost::{FreeSummonUse, OrbCosts},
+        goal::BudgetUnit,
+    };
+
     use super::*;
 
     fn median(counter: &FrequencyCounter) -> u32 {
@@ -358,6 +1076,16 @@ mod test {
             .0 as u32
     }
 
+    fn mean(counter: &FrequencyCounter) -> f64 {
+        let total = counter.iter().sum::<u32>() as f64;
+        counter
+            .iter()
+            .enumerate()
+            .map(|(i, &count)| i as f64 * count as f64)
+            .sum::<f64>()
+            / total
+    }
+
     fn high_percentile(counter: &FrequencyCounter) -> u32 {
         let total: u32 = counter.iter().sum();
         let mut cum_total = 0;
@@ -373,19 +1101,8 @@ mod test {
     }
 
     fn standard() -> (GenericBanner, Goal) {
-        let banner = StandardBanner::Standard {
-            focus: [1, 1, 1, 1],
-        }
-        .as_generic_banner(false);
-        let goal = Goal::Quantity(UnitCountGoal::new(
-            vec![UnitGoal {
-                color: Color::Red,
-                copies: 1,
-                pools: EnumSet::from(Pool::Focus),
-            }],
-            true,
-        ));
-        (banner, goal)
+        let (banner, goal) = test_banner_and_goal();
+        (banner, Goal::Quantity(goal))
     }
 
     #[test]
@@ -523,6 +1240,7 @@ mod test {
                 color: Color::Red,
                 copies: 1,
                 pools: EnumSet::from(Pool::Focus),
+                min_rarity: Rarity::ThreeStar,
             }),
             Goal::OrbBudget(_) => {}
         }
@@ -551,6 +1269,43 @@ mod test {
         assert!(medians.2 <= medians.1 * 2);
     }
 
+    #[test]
+    fn test_orb_limit_spark() {
+        let (mut banner, goal) = standard();
+        banner.has_spark = true;
+        let Goal::Quantity(goal) = goal else {
+            unreachable!()
+        };
+        let config = SimConfig {
+            strategy: Arc::new(ColorStrategy::All),
+            stop: StopPolicy::FinishSession,
+            start: StartState {
+                status: Status {
+                    total_pulled: 38,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let cache = DistributionCache::new(&banner);
+        let mut rng = Xoshiro128Plus::seed_from_u64(0);
+        for _ in 0..100 {
+            // The orbs run out after two summons, which are enough for the spark
+            let metrics = sim_until_goal(
+                &banner,
+                &mut goal.clone(),
+                9,
+                &config,
+                &mut rng,
+                &cache,
+                &mut (),
+            );
+            assert_eq!(metrics.summons, 40);
+            assert_eq!(metrics.goal_copies, 1);
+        }
+    }
+
     #[test]
     fn test_budget() {
         let banner = StandardBanner::Standard {
@@ -558,9 +1313,12 @@ mod test {
         }
         .as_generic_banner(false);
         let goal = Goal::OrbBudget(BudgetGoal {
-            color: Color::Red,
+            units: vec![BudgetUnit {
+                color: Color::Red,
+                pools: EnumSet::from(Pool::Focus),
+                min_rarity: Rarity::ThreeStar,
+            }],
             limit: BudgetGoalLimit::OrbCount(200),
-            pools: EnumSet::from(Pool::Focus),
         });
         let results = Sim::new(banner.clone(), goal.clone())
             .sim(10000)
@@ -588,4 +1346,1239 @@ mod test {
             assert!(median <= 11);
         }
     }
+
+    #[test]
+    fn test_strategy_pull_all() {
+        let (banner, goal) = standard();
+        let results = Sim::new(banner.clone(), goal.clone())
+            .sim(10000)
+            .data()
+            .clone();
+
+        let config = SimConfig {
+            strategy: Arc::new(ColorStrategy::All),
+            ..Default::default()
+        };
+        let results_pulling_all = Sim::new(banner, goal)
+            .with_config(config)
+            .sim(10000)
+            .data()
+            .clone();
+        let medians = dbg!(median(&results), median(&results_pulling_all));
+        assert!(medians.0 <= medians.1);
+    }
+
+    #[test]
+    fn test_free_summons() {
+        let (banner, goal) = standard();
+        let results = Sim::new(banner.clone(), goal.clone())
+            .sim(10000)
+            .data()
+            .clone();
+
+        let config = SimConfig {
+            free_summons: FreeSummons {
+                tickets: 10,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let results_with_tickets = Sim::new(banner.clone(), goal.clone())
+            .with_config(config)
+            .sim(10000)
+            .data()
+            .clone();
+        let medians = dbg!(median(&results_with_tickets), median(&results));
+        assert!(medians.0 < medians.1);
+
+        let config = SimConfig {
+            free_summons: FreeSummons {
+                free: 1000,
+                free_use: FreeSummonUse::AnySummon,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let results_all_free = Sim::new(banner, goal)
+            .with_config(config)
+            .sim(10000)
+            .data()
+            .clone();
+        assert_eq!(results_all_free[0], 10000);
+    }
+
+    #[test]
+    fn test_standard_banners() {
+        let focus = [1, 1, 1, 1];
+        let fourstar_focus = [1, 0, 0, 0];
+        // Starting rates, 4★ special rate, then spark and focus charges without and with FEH Pass
+        let banners = [
+            (
+                StandardBanner::Standard { focus },
+                (3, 3),
+                3,
+                [(false, true); 2],
+            ),
+            (
+                StandardBanner::NewHeroes {
+                    focus,
+                    fourstar_focus,
+                },
+                (3, 3),
+                3,
+                [(true, true); 2],
+            ),
+            (
+                StandardBanner::NewSeasonal {
+                    focus,
+                    fourstar_focus,
+                },
+                (3, 3),
+                3,
+                [(false, false), (true, true)],
+            ),
+            (
+                StandardBanner::WeeklyRevival { focus },
+                (4, 2),
+                3,
+                [(false, true); 2],
+            ),
+            (
+                StandardBanner::WeeklyFocus {
+                    focus,
+                    rates: (5, 1),
+                },
+                (5, 1),
+                3,
+                [(false, true); 2],
+            ),
+            (
+                StandardBanner::Attuned {
+                    focus,
+                    fourstar_focus,
+                },
+                (3, 3),
+                3,
+                [(true, true); 2],
+            ),
+            (
+                StandardBanner::Rearmed {
+                    focus,
+                    fourstar_focus,
+                },
+                (3, 3),
+                3,
+                [(true, true); 2],
+            ),
+            (
+                StandardBanner::Ascended {
+                    focus,
+                    fourstar_focus,
+                },
+                (3, 3),
+                3,
+                [(true, true); 2],
+            ),
+            (
+                StandardBanner::Emblem {
+                    focus,
+                    fourstar_focus,
+                },
+                (3, 3),
+                3,
+                [(true, true); 2],
+            ),
+            (
+                StandardBanner::SpecialRevival {
+                    focus,
+                    fourstar_focus,
+                },
+                (3, 3),
+                3,
+                [(false, false), (true, true)],
+            ),
+            (
+                StandardBanner::Legendary,
+                (8, 0),
+                3,
+                [(false, false), (true, false)],
+            ),
+            (
+                StandardBanner::LegendaryLegacy,
+                (8, 0),
+                0,
+                [(false, false), (true, false)],
+            ),
+            (
+                StandardBanner::HeroFest,
+                (5, 3),
+                3,
+                [(false, false), (true, true)],
+            ),
+            (
+                StandardBanner::LegendaryRemix,
+                (6, 0),
+                3,
+                [(true, false); 2],
+            ),
+            (
+                StandardBanner::DoubleSpecial { fourstar_focus },
+                (6, 0),
+                3,
+                [(false, false); 2],
+            ),
+        ];
+        for (banner, starting_rates, fourstar_special_rate, extras) in banners {
+            for (has_feh_pass, (has_spark, has_charges)) in [false, true].into_iter().zip(extras) {
+                let generic = banner.as_generic_banner(has_feh_pass);
+                assert!(generic.is_valid(), "{generic:?}");
+                assert_eq!(
+                    (
+                        generic.starting_rates,
+                        generic.fourstar_special_rate,
+                        generic.has_spark,
+                        generic.has_charges
+                    ),
+                    (
+                        starting_rates,
+                        fourstar_special_rate,
+                        has_spark,
+                        has_charges
+                    ),
+                    "{banner:?} with FEH Pass: {has_feh_pass}"
+                );
+            }
+        }
+        let attuned = StandardBanner::Attuned {
+            focus,
+            fourstar_focus,
+        };
+        assert_eq!(
+            attuned.as_generic_banner(false).fourstar_focus_sizes,
+            fourstar_focus
+        );
+
+        let goal = standard().1;
+        let fourstar_specials = |banner: StandardBanner| {
+            Sim::new(banner.as_generic_banner(false), goal.clone())
+                .sim(1000)
+                .metrics()
+                .get(Metric::FourstarSpecials)
+                .clone()
+        };
+        assert!(fourstar_specials(StandardBanner::Legendary)[0] < 1000);
+        assert_eq!(fourstar_specials(StandardBanner::LegendaryLegacy)[0], 1000);
+    }
+
+    #[test]
+    fn test_banner_validation() {
+        let (banner, goal) = standard();
+        assert_eq!(banner.validate(), vec![]);
+        assert_eq!(banner.validate_goal(&goal), vec![]);
+
+        let invalid = GenericBanner {
+            starting_rates: (0, 99),
+            focus_sizes: [0, 0, 0, 0],
+            fourstar_focus_sizes: [0, 2, 0, 0],
+            ..banner.clone()
+        };
+        assert!(!invalid.is_valid());
+        assert_eq!(
+            invalid.validate(),
+            vec![
+                BannerError::RatesOver100 { total: 105 },
+                BannerError::ZeroFocusRate,
+                BannerError::TooManyFourstarFocus {
+                    color: Color::Blue,
+                    fourstar_focus: 2,
+                    focus: 0
+                },
+                BannerError::EmptyFocusPool,
+            ]
+        );
+
+        let zero_rate_model = GenericBanner {
+            has_spark: true,
+            has_charges: true,
+            rate_model: RateModel {
+                pity_step_pulls: 0,
+                charge_threshold: 0,
+                spark_threshold: 0,
+                ..Default::default()
+            },
+            ..banner.clone()
+        };
+        assert_eq!(
+            zero_rate_model.validate(),
+            vec![
+                BannerError::ZeroPityStepPulls,
+                BannerError::ZeroChargeThreshold,
+                BannerError::ZeroSparkThreshold,
+            ]
+        );
+
+        let fourstar_focus = GenericBanner {
+            fourstar_focus_sizes: [1, 0, 0, 0],
+            fourstar_focus_rate: 10,
+            ..banner.clone()
+        };
+        assert_eq!(fourstar_focus.pool_rate(Pool::FourstarFocus), 10);
+        assert_eq!(fourstar_focus.pool_rate(Pool::Common), 81);
+        let too_many_fourstars = GenericBanner {
+            fourstar_focus_rate: 95,
+            ..fourstar_focus
+        };
+        assert_eq!(
+            too_many_fourstars.validate(),
+            vec![BannerError::RatesOver100 { total: 104 }]
+        );
+
+        // Pity has nothing to take from when every stone is already a 5★
+        let all_fivestar = StandardBanner::LegendaryLegacy.as_generic_banner(false);
+        let all_fivestar = GenericBanner {
+            starting_rates: (60, 40),
+            ..all_fivestar
+        };
+        assert_eq!(
+            all_fivestar.validate(),
+            vec![BannerError::NoRateForPity {
+                fivestar_total: 100
+            }]
+        );
+
+        let no_red = GenericBanner {
+            focus_sizes: [0, 1, 1, 1],
+            ..banner
+        };
+        assert!(no_red.is_valid());
+        assert_eq!(
+            no_red.validate_goal(&goal),
+            vec![BannerError::NoGoalUnits { color: Color::Red }]
+        );
+        // Red units can still come from the off-focus pool
+        let mut off_focus_goal = goal.clone();
+        if let Goal::Quantity(ref mut goal) = off_focus_goal {
+            goal.units[0].pools |= Pool::Fivestar;
+        }
+        assert_eq!(no_red.validate_goal(&off_focus_goal), vec![]);
+
+        // Nothing in the 4★ focus pool is a 5★
+        let banner = StandardBanner::NewHeroes {
+            focus: [1, 1, 1, 1],
+            fourstar_focus: [1, 0, 0, 0],
+        }
+        .as_generic_banner(false);
+        let mut fivestar_goal = UnitCountGoal::new(
+            vec![UnitGoal {
+                color: Color::Red,
+                copies: 1,
+                pools: EnumSet::from(Pool::FourstarFocus),
+                min_rarity: Rarity::FiveStar,
+            }],
+            true,
+        );
+        assert_eq!(
+            banner.validate_goal(&Goal::Quantity(fivestar_goal.clone())),
+            vec![BannerError::UnreachableRarity {
+                color: Color::Red,
+                min_rarity: Rarity::FiveStar
+            }]
+        );
+        fivestar_goal.units[0].pools |= Pool::Focus;
+        assert_eq!(banner.validate_goal(&Goal::Quantity(fivestar_goal)), vec![]);
+    }
+
+    #[test]
+    fn test_orb_costs() {
+        let (mut banner, goal) = standard();
+        let results = Sim::new(banner.clone(), goal.clone())
+            .sim(10000)
+            .data()
+            .clone();
+
+        banner.costs = OrbCosts::flat(1);
+        let metrics = Sim::new(banner.clone(), goal.clone())
+            .sim(10000)
+            .metrics()
+            .clone();
+        assert_eq!(metrics.get(Metric::OrbsSpent), metrics.get(Metric::Summons));
+
+        banner.costs = OrbCosts::default().with_first_summon(2);
+        let discounted = Sim::new(banner.clone(), goal).sim(10000).data().clone();
+        assert!(median(&discounted) < median(&results));
+
+        // The budget is spent down to the last orb when every stone costs one
+        banner.costs = OrbCosts::flat(1);
+        let budget = Goal::OrbBudget(BudgetGoal {
+            units: vec![BudgetUnit {
+                color: Color::Red,
+                pools: EnumSet::from(Pool::Focus),
+                min_rarity: Rarity::ThreeStar,
+            }],
+            limit: BudgetGoalLimit::OrbCount(101),
+        });
+        let metrics = Sim::new(banner, budget).sim(10000).metrics().clone();
+        assert_eq!(metrics.get(Metric::OrbsSpent)[101], 10000);
+        assert_eq!(metrics.get(Metric::Summons)[101], 10000);
+    }
+
+    #[test]
+    fn test_budget_tickets() {
+        let banner = StandardBanner::Standard {
+            focus: [1, 1, 1, 1],
+        }
+        .as_generic_banner(false);
+        let goal = Goal::OrbBudget(BudgetGoal {
+            units: vec![BudgetUnit {
+                color: Color::Red,
+                pools: EnumSet::from(Pool::Focus),
+                min_rarity: Rarity::ThreeStar,
+            }],
+            limit: BudgetGoalLimit::OrbCount(200),
+        });
+        let results = Sim::new(banner.clone(), goal.clone())
+            .sim(10000)
+            .data()
+            .clone();
+        let config = SimConfig {
+            free_summons: FreeSummons {
+                tickets: 40,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let results_with_tickets = Sim::new(banner, goal)
+            .with_config(config)
+            .sim(10000)
+            .data()
+            .clone();
+        let means = dbg!(mean(&results), mean(&results_with_tickets));
+        assert!(means.0 < means.1);
+    }
+
+    #[test]
+    fn test_trace() {
+        let (mut banner, goal) = standard();
+        banner.has_spark = true;
+        let results = Sim::new(banner.clone(), goal.clone())
+            .with_seed(3)
+            .sim(1000)
+            .data()
+            .clone();
+
+        let mut sim = Sim::new(banner, goal).with_seed(3);
+        let traces = sim.sim_traced(1000, |trace| trace.result >= 150);
+        // Tracing doesn't change which trials get run
+        assert_eq!(sim.data(), &results);
+        assert_eq!(traces.len() as u32, results.iter().skip(150).sum::<u32>());
+        for trace in traces {
+            let last = trace.sessions.last().unwrap();
+            assert_eq!(last.status_after.orbs_spent, trace.result);
+            assert_eq!(last.goal_copies, vec![0]);
+            for session in &trace.sessions {
+                assert!(!session.pulled.is_empty());
+                assert!(
+                    session.sparked_units.is_empty() || session.status_after.total_pulled >= 40
+                );
+                assert_eq!(
+                    session.status_after.total_pulled - session.status_before.total_pulled,
+                    session.pulled.len() as u32
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_trace_sparks() {
+        let (mut banner, mut goal) = standard();
+        banner.has_spark = true;
+        banner.rate_model.spark_threshold = 2;
+        banner.rate_model.spark_repeats = true;
+        if let Goal::Quantity(ref mut goal) = goal {
+            goal.units[0].copies = 20;
+        }
+        let mut sim = Sim::new(banner, goal).with_config(SimConfig {
+            strategy: Arc::new(ColorStrategy::All),
+            start: StartState {
+                sparks_available: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let traces = sim.sim_traced(10, |_| true);
+        for trace in traces {
+            // The spark the player started with is used before any summons
+            assert_eq!(trace.start_sparked_units, vec![0]);
+            // Every session of 5 summons earns at least 2 sparks
+            assert!(trace.sessions[0].sparked_units.len() >= 2);
+            assert!(trace
+                .sessions
+                .iter()
+                .filter(|session| session.pulled.len() == 5)
+                .all(|session| session.sparked_units.len() >= 2));
+        }
+    }
+
+    #[derive(Default)]
+    struct CountingObserver {
+        sessions: u32,
+        pulls: u32,
+        pity_resets: u32,
+        charges_gained: u32,
+        charges_spent: u32,
+        sparks: u32,
+        goals_finished: u32,
+        trials: u32,
+    }
+
+    impl Observer for CountingObserver {
+        fn session_start(&mut self, _stones: &[(Pool, Color); 5], _status: &Status) {
+            self.sessions += 1;
+        }
+
+        fn pulled(&mut self, _index: usize, _stone: (Pool, Color), _status: &Status) {
+            self.pulls += 1;
+        }
+
+        fn pity_reset(&mut self, pity_before: u32, status: &Status) {
+            assert!(status.pity_count < pity_before);
+            self.pity_resets += 1;
+        }
+
+        fn focus_charge_gained(&mut self, _status: &Status) {
+            self.charges_gained += 1;
+        }
+
+        fn focus_charges_spent(&mut self, _status: &Status) {
+            self.charges_spent += 1;
+        }
+
+        fn spark_used(&mut self, _unit: usize) {
+            self.sparks += 1;
+        }
+
+        fn goal_finished(&mut self, _status: &Status) {
+            self.goals_finished += 1;
+        }
+
+        fn trial_end(&mut self, _metrics: &TrialMetrics) {
+            self.trials += 1;
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let (mut banner, goal) = standard();
+        banner.has_spark = true;
+        let results = Sim::new(banner.clone(), goal.clone())
+            .with_seed(5)
+            .sim(1000)
+            .data()
+            .clone();
+
+        let mut observer = CountingObserver::default();
+        let mut sim = Sim::new(banner, goal).with_seed(5);
+        sim.sim_observed(1000, &mut observer);
+        assert_eq!(sim.data(), &results);
+        let metrics = sim.metrics();
+        let total = |metric| {
+            metrics
+                .get(metric)
+                .iter()
+                .enumerate()
+                .map(|(value, &count)| value as u32 * count)
+                .sum::<u32>()
+        };
+        assert_eq!(observer.trials, 1000);
+        assert_eq!(observer.goals_finished, 1000);
+        assert_eq!(observer.pulls, total(Metric::Summons));
+        assert_eq!(observer.sparks, total(Metric::SparksUsed));
+        assert!(observer.sessions * 5 >= observer.pulls && observer.sessions <= observer.pulls);
+        // Almost every trial ends on a focus 5★ unless a spark finished it, and other colors'
+        // focus units and off-focus 5★ units take away pity too
+        assert!(observer.pity_resets > 1000 - observer.sparks);
+        assert!(observer.charges_gained >= observer.charges_spent);
+        assert!(observer.charges_spent > 0);
+
+        // Goal units pulled while finishing the session don't finish the goal again
+        let banner = StandardBanner::Standard {
+            focus: [2, 1, 1, 1],
+        }
+        .as_generic_banner(false);
+        let unit = UnitGoal {
+            color: Color::Red,
+            copies: 1,
+            pools: EnumSet::from(Pool::Focus),
+            min_rarity: Rarity::ThreeStar,
+        };
+        let goal = Goal::Quantity(UnitCountGoal::new(vec![unit, unit], false));
+        let mut observer = CountingObserver::default();
+        let mut sim = Sim::new(banner, goal).with_seed(5).with_config(SimConfig {
+            stop: StopPolicy::FinishSession,
+            ..Default::default()
+        });
+        sim.sim_observed(1000, &mut observer);
+        let goal_copies = sim.metrics().get(Metric::GoalCopies);
+        assert!(goal_copies.iter().skip(2).sum::<u32>() > 0);
+        assert_eq!(observer.goals_finished, 1000);
+
+        // Pity resets come from the pool's effect on pity, not from its units being 5★
+        let (banner, goal) = standard();
+        let resetting = GenericBanner {
+            extra_pools: vec![PoolSpec {
+                kind: Pool::FourstarSpecial,
+                rate: 20,
+                sizes: [1, 1, 1, 1],
+                pity: PityEffect::Reset,
+                charges: ChargeEffect::None,
+            }],
+            ..banner
+        };
+        let mut observer = CountingObserver::default();
+        let mut sim = Sim::new(resetting, goal).with_seed(5);
+        sim.sim_observed(1000, &mut observer);
+        assert!(observer.pity_resets > 3000);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let (mut banner, goal) = standard();
+        banner.has_spark = true;
+        let mut sim = Sim::new(banner, goal);
+        sim.sim(10000);
+        let metrics = sim.metrics();
+        assert_eq!(metrics.get(Metric::OrbsSpent), sim.data());
+        assert_eq!(metrics.get(Metric::GoalCopies)[1], 10000);
+        assert_eq!(metrics.get(Metric::SparksUsed).iter().sum::<u32>(), 10000);
+        assert!(metrics.get(Metric::SparksUsed)[1] > 0);
+        // 2.5% per summon on the first summons, so it's a big surprise to see none at all
+        assert!(metrics.get(Metric::FourstarSpecials)[0] < 10000);
+        assert!(metrics.get(Metric::Summons).len() <= 41);
+        assert!(metrics.get(Metric::MaxPity).len() <= 9);
+        assert!(mean(metrics.get(Metric::OffFocusFivestars)) < 2.0);
+    }
+
+    #[test]
+    fn test_spark_policy() {
+        let mut banner = StandardBanner::NewHeroes {
+            focus: [1, 1, 1, 1],
+            fourstar_focus: [1, 0, 0, 0],
+        }
+        .as_generic_banner(false);
+        banner.has_spark = true;
+        // The red unit is also a 4★ focus, so it's much easier to pull than the blue one
+        let goal = UnitCountGoal::new(
+            vec![
+                UnitGoal {
+                    color: Color::Red,
+                    copies: 1,
+                    pools: Pool::Focus | Pool::FourstarFocus,
+                    min_rarity: Rarity::ThreeStar,
+                },
+                UnitGoal {
+                    color: Color::Blue,
+                    copies: 1,
+                    pools: EnumSet::from(Pool::Focus),
+                    min_rarity: Rarity::ThreeStar,
+                },
+            ],
+            true,
+        );
+        let sim = |spark| {
+            let config = SimConfig {
+                spark,
+                ..Default::default()
+            };
+            let mut sim = Sim::new(banner.clone(), Goal::Quantity(goal.clone()))
+                .with_seed(0)
+                .with_config(config);
+            sim.sim(10000);
+            sim.metrics().clone()
+        };
+
+        // When both units are still needed at the spark, only the default picks the red one
+        let most_needed = sim(SparkPolicy::MostCopiesNeeded);
+        let lowest_chance = sim(SparkPolicy::LowestPullChance);
+        assert!(most_needed.sparked_units()[0] > lowest_chance.sparked_units()[0]);
+        assert!(most_needed.sparked_units()[1] < lowest_chance.sparked_units()[1]);
+        assert!(
+            median(lowest_chance.get(Metric::OrbsSpent))
+                <= median(most_needed.get(Metric::OrbsSpent))
+        );
+        let fixed = sim(SparkPolicy::Fixed(1));
+        assert!(fixed.sparked_units()[1] > fixed.sparked_units()[0]);
+        for metrics in [most_needed, lowest_chance, fixed] {
+            let sparks = metrics.get(Metric::SparksUsed);
+            assert_eq!(
+                metrics.sparked_units().iter().sum::<u32>(),
+                sparks
+                    .iter()
+                    .enumerate()
+                    .map(|(n, &count)| n as u32 * count)
+                    .sum::<u32>()
+            );
+        }
+
+        // Saving the spark makes no difference when it finishes the goal either way
+        let (mut banner, goal) = standard();
+        banner.has_spark = true;
+        let saved = Sim::new(banner.clone(), goal.clone())
+            .with_config(SimConfig {
+                spark: SparkPolicy::SaveUntilEnd,
+                ..Default::default()
+            })
+            .sim(10000)
+            .data()
+            .clone();
+        let immediate = Sim::new(banner, goal).sim(10000).data().clone();
+        assert!(saved.len() <= 201);
+        assert!(median(&saved).abs_diff(median(&immediate)) <= 10);
+    }
+
+    #[test]
+    fn test_budget_multiple_units() {
+        let mut banner = StandardBanner::Standard {
+            focus: [2, 1, 1, 1],
+        }
+        .as_generic_banner(false);
+        banner.has_spark = true;
+        let unit = |color| BudgetUnit {
+            color,
+            pools: EnumSet::from(Pool::Focus),
+            min_rarity: Rarity::ThreeStar,
+        };
+        let goal = BudgetGoal {
+            units: vec![unit(Color::Red), unit(Color::Red), unit(Color::Blue)],
+            limit: BudgetGoalLimit::OrbCount(300),
+        };
+        let mut sim = Sim::new(banner.clone(), Goal::OrbBudget(goal.clone()));
+        sim.sim(10000);
+        let metrics = sim.metrics();
+        let unit_copies = metrics.unit_copies();
+        assert_eq!(unit_copies.len(), 3);
+        let total_mean = unit_copies.iter().map(mean).sum::<f64>();
+        assert!((total_mean - mean(sim.data())).abs() < 1e-6);
+        // Both red units are equally likely to be pulled, but ties for the spark go to the first
+        assert!(mean(&unit_copies[0]) > mean(&unit_copies[1]));
+        assert!(mean(&unit_copies[1]) > 0.0);
+        assert_eq!(metrics.sparked_units().iter().sum::<u32>(), 10000);
+        assert!(metrics.sparked_units()[2] > 0);
+
+        let single_unit = Sim::new(
+            banner.clone(),
+            Goal::OrbBudget(BudgetGoal {
+                units: vec![unit(Color::Red)],
+                ..goal.clone()
+            }),
+        )
+        .sim(10000)
+        .data()
+        .clone();
+        assert!(median(sim.data()) > median(&single_unit));
+
+        let fixed = Sim::new(banner, Goal::OrbBudget(goal))
+            .with_config(SimConfig {
+                spark: SparkPolicy::Fixed(2),
+                ..Default::default()
+            })
+            .sim(10000)
+            .metrics()
+            .clone();
+        assert_eq!(fixed.sparked_units()[2], 10000);
+        assert!(mean(&fixed.unit_copies()[2]) > mean(&unit_copies[2]));
+    }
+
+    #[test]
+    fn test_whole_banner() {
+        let legendary = StandardBanner::Legendary.as_generic_banner(false);
+        let goal = BudgetGoal::whole_banner(&legendary, BudgetGoalLimit::OrbCount(1000));
+        assert_eq!(goal.units.len(), 12);
+        assert_eq!(goal.units[3].color, Color::Blue);
+        // Units of extra focus pools are part of the banner too
+        let boosted = GenericBanner {
+            extra_pools: vec![PoolSpec {
+                kind: Pool::Focus,
+                rate: 3,
+                sizes: [1, 0, 0, 0],
+                pity: PityEffect::Reset,
+                charges: ChargeEffect::Spend,
+            }],
+            ..legendary
+        };
+        let goal = BudgetGoal::whole_banner(&boosted, BudgetGoalLimit::OrbCount(1000));
+        assert_eq!(goal.units.len(), 13);
+        assert_eq!(goal.units[3].color, Color::Red);
+
+        let banner = StandardBanner::NewHeroes {
+            focus: [1, 1, 1, 1],
+            fourstar_focus: [1, 0, 0, 0],
+        }
+        .as_generic_banner(false);
+        let goal = BudgetGoal::whole_banner(&banner, BudgetGoalLimit::OrbCount(1000));
+        assert_eq!(goal.units[0].pools, Pool::Focus | Pool::FourstarFocus);
+        assert_eq!(goal.units[1].pools, EnumSet::from(Pool::Focus));
+        let mut sim = Sim::new(banner, Goal::OrbBudget(goal));
+        sim.sim(10000);
+        let metrics = sim.metrics();
+        let unit_copies = metrics.unit_copies();
+        assert_eq!(unit_copies.len(), 4);
+        let total_mean = unit_copies.iter().map(mean).sum::<f64>();
+        assert!((total_mean - mean(sim.data())).abs() < 1e-6);
+        // The red unit can also be pulled from the 4★ focus pool
+        assert!(mean(&unit_copies[0]) > mean(&unit_copies[1]) + 1.0);
+        assert!(unit_copies[1..].iter().all(|copies| mean(copies) > 1.0));
+        assert!(mean(metrics.get(Metric::OffFocusFivestars)) > 1.0);
+        assert!(mean(metrics.get(Metric::FourstarSpecials)) > 1.0);
+        assert!(mean(metrics.get(Metric::Commons)) > 10.0);
+    }
+
+    #[test]
+    fn test_rate_model() {
+        let (mut banner, goal) = standard();
+        assert_eq!(banner.rate_model, RateModel::default());
+        let results = Sim::new(banner.clone(), goal.clone())
+            .sim(10000)
+            .data()
+            .clone();
+
+        banner.rate_model.pity_step_increase = 100;
+        let results_with_more_pity = Sim::new(banner.clone(), goal.clone())
+            .sim(10000)
+            .data()
+            .clone();
+        let medians = dbg!(median(&results_with_more_pity), median(&results));
+        assert!(medians.0 < medians.1);
+
+        banner.rate_model = RateModel {
+            spark_threshold: 20,
+            ..Default::default()
+        };
+        banner.has_spark = true;
+        let results_with_early_spark = Sim::new(banner.clone(), goal.clone())
+            .sim(10000)
+            .data()
+            .clone();
+        assert!(results_with_early_spark.len() <= 101);
+
+        banner.rate_model = RateModel {
+            max_pity_steps: 2,
+            ..Default::default()
+        };
+        banner.has_spark = false;
+        let metrics = Sim::new(banner, goal).sim(10000).metrics().clone();
+        assert!(metrics.get(Metric::MaxPity).len() <= 3);
+    }
+
+    #[test]
+    fn test_repeating_spark() {
+        let banner = StandardBanner::NewHeroes {
+            focus: [1, 1, 1, 1],
+            fourstar_focus: [0, 0, 0, 0],
+        }
+        .as_generic_banner(false);
+        let mut goal = UnitCountGoal::new(
+            vec![UnitGoal {
+                color: Color::Red,
+                copies: 3,
+                pools: EnumSet::from(Pool::Focus),
+                min_rarity: Rarity::ThreeStar,
+            }],
+            true,
+        );
+        let once = Sim::new(banner.clone(), Goal::Quantity(goal.clone()))
+            .sim(10000)
+            .metrics()
+            .clone();
+        assert!(once.get(Metric::Summons).len() > 121);
+
+        let mut repeating_banner = banner.clone();
+        repeating_banner.rate_model.spark_repeats = true;
+        let repeating = Sim::new(repeating_banner.clone(), Goal::Quantity(goal.clone()))
+            .sim(10000)
+            .metrics()
+            .clone();
+        // Every 40 summons guarantee a copy
+        assert!(repeating.get(Metric::Summons).len() <= 121);
+        assert!(repeating.get(Metric::SparksUsed).len() == 4);
+        assert!(
+            high_percentile(repeating.get(Metric::OrbsSpent))
+                < high_percentile(once.get(Metric::OrbsSpent))
+        );
+
+        // A spark that finishes the goal ends the session early
+        goal.units[0].copies = 1;
+        repeating_banner.rate_model.spark_threshold = 7;
+        let metrics = Sim::new(repeating_banner.clone(), Goal::Quantity(goal))
+            .sim(10000)
+            .metrics()
+            .clone();
+        assert!(metrics.get(Metric::Summons).len() <= 8);
+
+        let budget = BudgetGoal {
+            units: vec![BudgetUnit {
+                color: Color::Red,
+                pools: EnumSet::from(Pool::Focus),
+                min_rarity: Rarity::ThreeStar,
+            }],
+            limit: BudgetGoalLimit::OrbCount(1000),
+        };
+        let once = Sim::new(banner, Goal::OrbBudget(budget.clone()))
+            .sim(10000)
+            .metrics()
+            .clone();
+        assert_eq!(once.get(Metric::SparksUsed)[1], 10000);
+        let repeating = Sim::new(repeating_banner.clone(), Goal::OrbBudget(budget.clone()))
+            .sim(10000)
+            .metrics()
+            .clone();
+        assert!(median(repeating.get(Metric::SparksUsed)) > 20);
+        assert!(median(repeating.get(Metric::GoalCopies)) > median(once.get(Metric::GoalCopies)));
+
+        // Past the first spark, pulling until the spark goes on to the next one, at 56 summons
+        let until_spark = BudgetGoal {
+            limit: BudgetGoalLimit::UntilSpark,
+            ..budget
+        };
+        let metrics = Sim::new(repeating_banner, Goal::OrbBudget(until_spark))
+            .with_start(StartState {
+                status: Status {
+                    total_pulled: 50,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .sim(1000)
+            .metrics()
+            .clone();
+        assert_eq!(metrics.get(Metric::SparksUsed)[1], 1000);
+        let summons = metrics.get(Metric::Summons);
+        assert_eq!(summons.iter().skip(56).take(5).sum::<u32>(), 1000);
+    }
+
+    #[test]
+    fn test_start_state() {
+        let (mut banner, goal) = standard();
+        let results = Sim::new(banner.clone(), goal.clone())
+            .sim(10000)
+            .data()
+            .clone();
+
+        let start = |status, goal_copies, sparks_available| StartState {
+            status,
+            goal_copies,
+            sparks_available,
+        };
+        let with_pity = Status {
+            pity_count: 100,
+            ..Default::default()
+        };
+        let results_with_pity = Sim::new(banner.clone(), goal.clone())
+            .with_start(start(with_pity, vec![], 0))
+            .sim(10000)
+            .data()
+            .clone();
+        let medians = dbg!(median(&results_with_pity), median(&results));
+        assert!(medians.0 < medians.1);
+
+        let done = Sim::new(banner.clone(), goal.clone())
+            .with_start(start(Status::default(), vec![1], 0))
+            .sim(1000)
+            .data()
+            .clone();
+        assert_eq!(done[0], 1000);
+        let with_spark = Sim::new(banner.clone(), goal.clone())
+            .with_start(start(Status::default(), vec![], 1))
+            .sim(1000)
+            .metrics()
+            .clone();
+        assert_eq!(with_spark.get(Metric::OrbsSpent)[0], 1000);
+        assert_eq!(with_spark.sparked_units()[0], 1000);
+
+        banner.has_spark = true;
+        let near_spark = Status {
+            total_pulled: 39,
+            ..Default::default()
+        };
+        let results_near_spark = Sim::new(banner.clone(), goal)
+            .with_start(start(near_spark, vec![], 0))
+            .sim(10000)
+            .data()
+            .clone();
+        assert!(results_near_spark.len() <= 21);
+
+        let unit = BudgetUnit {
+            color: Color::Red,
+            pools: EnumSet::from(Pool::Focus),
+            min_rarity: Rarity::ThreeStar,
+        };
+        let budget = |limit| {
+            Goal::OrbBudget(BudgetGoal {
+                units: vec![unit],
+                limit,
+            })
+        };
+        let copies = Sim::new(banner.clone(), budget(BudgetGoalLimit::OrbCount(100)))
+            .with_start(start(Status::default(), vec![2], 0))
+            .sim(1000)
+            .data()
+            .clone();
+        assert_eq!(copies[0] + copies[1], 0);
+        let past_spark = Status {
+            total_pulled: 50,
+            ..Default::default()
+        };
+        let metrics = Sim::new(banner, budget(BudgetGoalLimit::UntilSpark))
+            .with_start(start(past_spark, vec![1], 0))
+            .sim(1000)
+            .metrics()
+            .clone();
+        assert_eq!(metrics.get(Metric::GoalCopies)[1], 1000);
+        assert_eq!(metrics.get(Metric::Summons)[50], 1000);
+    }
+
+    #[test]
+    fn test_stop_policy() {
+        let (banner, goal) = standard();
+        let with_stop = |stop| SimConfig {
+            stop,
+            ..Default::default()
+        };
+        let results = Sim::new(banner.clone(), goal.clone())
+            .sim(10000)
+            .data()
+            .clone();
+        let results_finishing = Sim::new(banner.clone(), goal.clone())
+            .with_config(with_stop(StopPolicy::FinishSession))
+            .sim(10000)
+            .data()
+            .clone();
+        let means = dbg!(mean(&results_finishing), mean(&results));
+        assert!(means.0 > means.1);
+
+        // Capping each session at 5 orbs means one summon per session
+        let metrics = Sim::new(banner.clone(), goal.clone())
+            .with_config(with_stop(StopPolicy::SessionOrbCap(5)))
+            .sim(10000)
+            .metrics()
+            .clone();
+        for (orbs, &count) in metrics.get(Metric::OrbsSpent).iter().enumerate() {
+            assert!(count == 0 || orbs % 5 == 0);
+        }
+
+        let is_fivestar = |pool| matches!(pool, Pool::Focus | Pool::Fivestar);
+        let traces = Sim::new(banner.clone(), goal.clone())
+            .with_config(with_stop(StopPolicy::AnyFivestar))
+            .sim_traced(1000, |_| true);
+        for session in traces.iter().flat_map(|trace| &trace.sessions) {
+            let (_, earlier) = session.pulled.split_last().unwrap();
+            assert!(earlier
+                .iter()
+                .all(|&idx| !is_fivestar(session.stones[idx].0)));
+        }
+
+        let traces = Sim::new(banner, goal)
+            .with_config(with_stop(StopPolicy::PityReset))
+            .sim_traced(1000, |_| true);
+        for session in traces.iter().flat_map(|trace| &trace.sessions) {
+            if session.status_before.pity_count >= 5 {
+                let (_, earlier) = session.pulled.split_last().unwrap();
+                assert!(earlier
+                    .iter()
+                    .all(|&idx| session.stones[idx].0 != Pool::Focus));
+            }
+        }
+    }
+
+    #[test]
+    fn test_rarity() {
+        let mut banner = StandardBanner::NewHeroes {
+            focus: [1, 1, 1, 1],
+            fourstar_focus: [1, 0, 0, 0],
+        }
+        .as_generic_banner(false);
+        banner.has_spark = false;
+        let goal = |pools, min_rarity| {
+            Goal::Quantity(UnitCountGoal::new(
+                vec![UnitGoal {
+                    color: Color::Red,
+                    copies: 3,
+                    pools,
+                    min_rarity,
+                }],
+                true,
+            ))
+        };
+        let sim = |goal| Sim::new(banner.clone(), goal).sim(10000).metrics().clone();
+
+        // A common unit for skill inheritance
+        let any_common = sim(goal(EnumSet::from(Pool::Common), Rarity::ThreeStar));
+        let fourstar_common = sim(goal(EnumSet::from(Pool::Common), Rarity::FourStar));
+        let medians = dbg!(
+            median(fourstar_common.get(Metric::OrbsSpent)),
+            median(any_common.get(Metric::OrbsSpent))
+        );
+        assert!(medians.0 > medians.1);
+        assert!(any_common.get(Metric::ThreestarGoalCopies)[0] < 10000);
+        assert_eq!(fourstar_common.get(Metric::ThreestarGoalCopies)[0], 10000);
+        assert_eq!(fourstar_common.get(Metric::FourstarGoalCopies)[3], 10000);
+
+        // The 4★ focus unit's copies are reported apart from its 5★ ones
+        let focus = Pool::Focus | Pool::FourstarFocus;
+        let any_focus = sim(goal(focus, Rarity::ThreeStar));
+        assert!(any_focus.get(Metric::FourstarGoalCopies)[0] < 10000);
+        assert!(any_focus.get(Metric::FivestarGoalCopies)[0] < 10000);
+        let fivestar_focus = sim(goal(focus, Rarity::FiveStar));
+        assert_eq!(fivestar_focus.get(Metric::FourstarGoalCopies)[0], 10000);
+        assert_eq!(fivestar_focus.get(Metric::FivestarGoalCopies)[3], 10000);
+    }
+
+    #[test]
+    fn test_stopping_rule() {
+        let (banner, goal) = standard();
+        let rule = StoppingRule {
+            max_width: 10,
+            ..Default::default()
+        };
+        let mut sim = Sim::new(banner.clone(), goal.clone());
+        sim.sim_until_precise(&rule, 1000);
+        let total = sim.data().total();
+        assert!(total >= rule.min_iters && total < rule.max_iters);
+        let intervals = sim.percentile_intervals(&rule);
+        assert_eq!(intervals.len(), rule.percentiles.len());
+        for (&p, &(low, high)) in rule.percentiles.iter().zip(intervals.iter()) {
+            let value = sim.data().percentile(p).unwrap();
+            assert!(low <= value && value <= high);
+            assert!(high - low <= rule.max_width);
+        }
+
+        let rule = StoppingRule {
+            max_width: 0,
+            max_iters: 5000,
+            ..Default::default()
+        };
+        let mut sim = Sim::new(banner, goal);
+        sim.sim_until_precise(&rule, 3000);
+        assert_eq!(sim.data().total(), 5000);
+    }
+
+    #[test]
+    fn test_seed_reproducible() {
+        let (banner, goal) = standard();
+        let run = |seed| {
+            Sim::new(banner.clone(), goal.clone())
+                .with_seed(seed)
+                .sim(1000)
+                .sim(1000)
+                .data()
+                .clone()
+        };
+        assert_eq!(run(1), run(1));
+        assert_ne!(run(1), run(2));
+
+        // Each chunk uses its own stream, so the second chunk doesn't repeat the first
+        let mut sim = Sim::new(banner, goal).with_seed(1);
+        let first = sim.sim(1000).data().clone();
+        let both = sim.sim(1000).data().clone();
+        let second = both
+            .iter()
+            .enumerate()
+            .map(|(i, &count)| count - first[i as u32])
+            .collect::<Vec<_>>();
+        assert_ne!(second, *first);
+    }
+
+    #[test]
+    fn test_extra_pools() {
+        let (banner, goal) = standard();
+        assert_eq!(
+            banner
+                .pools()
+                .iter()
+                .map(|pool| (pool.kind, pool.rate))
+                .collect::<Vec<_>>(),
+            vec![
+                (Pool::Focus, 3),
+                (Pool::Fivestar, 3),
+                (Pool::FourstarFocus, 0),
+                (Pool::FourstarSpecial, 3),
+                (Pool::Common, 91),
+            ]
+        );
+
+        // The red unit gets a pool of its own with double the focus rate
+        let red_pool = PoolSpec {
+            kind: Pool::Focus,
+            rate: 6,
+            sizes: [1, 0, 0, 0],
+            pity: PityEffect::Reset,
+            charges: ChargeEffect::Spend,
+        };
+        let boosted = GenericBanner {
+            focus_sizes: [0, 1, 1, 1],
+            extra_pools: vec![red_pool],
+            ..banner.clone()
+        };
+        assert!(boosted.is_valid());
+        assert_eq!(boosted.validate_goal(&goal), vec![]);
+        assert_eq!(boosted.pool_sizes(Pool::Focus), [1, 1, 1, 1]);
+        assert_eq!(boosted.pool_rate(Pool::Focus), 9);
+        assert_eq!(boosted.pool_rate(Pool::Common), 85);
+
+        // Pity raises every 5★ pool in proportion to its rate
+        let pools = boosted.pools();
+        let rate_model = &boosted.rate_model;
+        let rates = pool_rates(&pools, rate_model, 10, false);
+        assert!((rates[5] - 2.0 * rates[0]).abs() < 1e-9);
+        assert!((rates[5] - (0.06 + 0.05 * 6.0 / 12.0)).abs() < 1e-9);
+        let max_pity = pool_rates(&pools, rate_model, rate_model.max_pity_steps, false);
+        assert!((max_pity[0] + max_pity[1] + max_pity[5] - 1.0).abs() < 1e-9);
+
+        let boosted_results = Sim::new(boosted, goal.clone()).sim(10000).data().clone();
+        let results = Sim::new(banner.clone(), goal.clone())
+            .sim(10000)
+            .data()
+            .clone();
+        let medians = dbg!(median(&boosted_results), median(&results));
+        assert!(medians.0 * 3 < medians.1);
+
+        // A second red focus unit in its own pool isn't the goal's unit, so pulling it only
+        // costs orbs and resets pity
+        let second_red = GenericBanner {
+            extra_pools: vec![red_pool],
+            ..banner.clone()
+        };
+        let results_second = Sim::new(second_red, goal.clone()).sim(10000).data().clone();
+        let medians = dbg!(median(&results_second), median(&results));
+        assert!(medians.0 > medians.1);
+
+        let empty = GenericBanner {
+            extra_pools: vec![PoolSpec {
+                sizes: [0, 0, 0, 0],
+                rate: 95,
+                ..red_pool
+            }],
+            ..banner
+        };
+        assert_eq!(
+            empty.validate(),
+            vec![
+                BannerError::RatesOver100 { total: 104 },
+                BannerError::NoRateForPity {
+                    fivestar_total: 101
+                },
+                BannerError::EmptyPool { pool: Pool::Focus },
+            ]
+        );
+
+        // The shared pools belong to the banner too
+        let mut no_commons = standard().0;
+        no_commons.base_pool_sizes.common = [0, 0, 0, 0];
+        assert_eq!(
+            no_commons.validate(),
+            vec![BannerError::EmptyPool { pool: Pool::Common }]
+        );
+        let mut no_red_fivestars = standard().0;
+        no_red_fivestars.base_pool_sizes.fivestar[Color::Red as usize] = 0;
+        assert_eq!(no_red_fivestars.pool_sizes(Pool::Fivestar), [0, 29, 20, 16]);
+    }
 }
@@ -0,0 +1,321 @@
+use std::fmt::Debug;
+
+use enumset::EnumSet;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    banner::{GenericBanner, RateModel},
+    goal::{BudgetGoal, UnitCountGoal, UnitGoal},
+    sim::Status,
+    types::{Color, Pool},
+};
+
+/// Everything a [`PullStrategy`] can look at when deciding whether to pull a stone.
+pub struct SessionState<'a> {
+    pub banner: &'a GenericBanner,
+    /// The five stones of the current session, in the order they're offered.
+    pub session: &'a [(Pool, Color); 5],
+    /// How many stones have already been pulled from this session.
+    pub num_pulled: u32,
+    pub status: &'a Status,
+    /// Colors of the goal units that still need more copies.
+    pub goal_colors: EnumSet<Color>,
+    /// How far along the goal is.
+    pub goal: GoalProgress<'a>,
+}
+
+/// The goal as of the current stone, for strategies that react to what's still needed.
+#[derive(Copy, Clone, Debug)]
+pub enum GoalProgress<'a> {
+    /// The copies each unit still needs, with 0 for units that are done.
+    Quantity(&'a UnitCountGoal),
+    /// The goal's units, and the copies of each pulled so far, by the unit's index in the goal.
+    OrbBudget {
+        goal: &'a BudgetGoal,
+        copies: &'a [u32],
+    },
+}
+
+/// Decides which stones of a summoning session to pull.
+pub trait PullStrategy: Debug + Send + Sync {
+    /// Whether to pull the stone at `index`. Stones are offered in order, and a stone that is
+    /// passed over isn't offered again.
+    ///
+    /// Every session needs at least one summon, so if nothing has been pulled by the last stone,
+    /// the simulator pulls it regardless of what this returns.
+    fn should_pull(&self, state: &SessionState, index: usize) -> bool;
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorStrategy {
+    /// Only pull stones of the goal's colors.
+    #[default]
+    GoalColors,
+    /// Pull stones of the goal's colors. If the session has none, pull the first stone of the
+    /// earliest-listed of these colors that is present instead of the last stone.
+    GoalColorsOrFallback(Vec<Color>),
+    /// Pull every stone.
+    All,
+    /// Pull stones of the goal's colors and of one other color, e.g. to build up focus charges.
+    GoalColorsAnd(Color),
+}
+
+impl PullStrategy for ColorStrategy {
+    fn should_pull(&self, state: &SessionState, index: usize) -> bool {
+        let color = state.session[index].1;
+        match self {
+            ColorStrategy::GoalColors => state.goal_colors.contains(color),
+            ColorStrategy::GoalColorsOrFallback(fallbacks) => {
+                if state.goal_colors.contains(color) {
+                    return true;
+                }
+                if state.num_pulled > 0
+                    || state
+                        .session
+                        .iter()
+                        .any(|&(_, color)| state.goal_colors.contains(color))
+                {
+                    return false;
+                }
+                let fallback = fallbacks
+                    .iter()
+                    .find(|&&fallback| state.session.iter().any(|&(_, color)| color == fallback));
+                fallback == Some(&color)
+                    && state.session[..index]
+                        .iter()
+                        .all(|&(_, other)| other != color)
+            }
+            ColorStrategy::All => true,
+            ColorStrategy::GoalColorsAnd(extra) => {
+                state.goal_colors.contains(color) || color == *extra
+            }
+        }
+    }
+}
+
+/// Which goal unit to take when a spark becomes available.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SparkPolicy {
+    /// The unit with the most copies still needed, or the first of them if there's a tie.
+    #[default]
+    MostCopiesNeeded,
+    /// The unit that is least likely to be pulled, e.g. to avoid sparking a unit that is also a
+    /// 4★ focus.
+    LowestPullChance,
+    /// The unit at this index in the goal, as long as it still needs copies.
+    Fixed(usize),
+    /// Keep the spark until it finishes the goal, or until the orbs run out.
+    SaveUntilEnd,
+}
+
+impl SparkPolicy {
+    /// Picks the index of the goal unit to spark, or `None` to save the spark for later.
+    pub fn choose(&self, banner: &GenericBanner, goal: &UnitCountGoal) -> Option<usize> {
+        self.choose_from(banner, &goal.units, goal.need_all)
+    }
+
+    pub(crate) fn choose_from(
+        &self,
+        banner: &GenericBanner,
+        units: &[UnitGoal],
+        need_all: bool,
+    ) -> Option<usize> {
+        let needed = || units.iter().enumerate().filter(|(_, unit)| unit.copies > 0);
+        match self {
+            SparkPolicy::MostCopiesNeeded => {
+                // max_by_key picks the last of equal units, so compare indices in reverse
+                needed()
+                    .max_by_key(|&(idx, unit)| (unit.copies, std::cmp::Reverse(idx)))
+                    .map(|(idx, _)| idx)
+            }
+            SparkPolicy::LowestPullChance => {
+                let chance = |unit: &UnitGoal| {
+                    unit.pools
+                        .iter()
+                        .map(|pool| {
+                            let size: f64 = banner.pool_sizes(pool).iter().map(|&s| s as f64).sum();
+                            if size > 0.0 {
+                                banner.pool_rate(pool) as f64 / size
+                                    * banner.rarity_chance(pool, unit.min_rarity)
+                            } else {
+                                0.0
+                            }
+                        })
+                        .sum::<f64>()
+                };
+                needed()
+                    .min_by(|(_, a), (_, b)| {
+                        chance(a)
+                            .total_cmp(&chance(b))
+                            .then(b.copies.cmp(&a.copies))
+                    })
+                    .map(|(idx, _)| idx)
+            }
+            SparkPolicy::Fixed(idx) => match units.get(*idx) {
+                Some(unit) if unit.copies > 0 => Some(*idx),
+                _ => SparkPolicy::MostCopiesNeeded.choose_from(banner, units, need_all),
+            },
+            SparkPolicy::SaveUntilEnd => {
+                if need_all {
+                    if units.iter().map(|unit| unit.copies).sum::<u32>() == 1 {
+                        return needed().next().map(|(idx, _)| idx);
+                    }
+                    None
+                } else {
+                    needed()
+                        .find(|(_, unit)| unit.copies == 1)
+                        .map(|(idx, _)| idx)
+                }
+            }
+        }
+    }
+}
+
+/// When to stop pulling on a session early, and whether to finish the session the goal is met in.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StopPolicy {
+    /// Stop as soon as the goal is met, even in the middle of a session.
+    #[default]
+    GoalMet,
+    /// When the goal is met, pull the rest of the session's stones anyway.
+    FinishSession,
+    /// Move on to a new session once the pity rate goes back to the starting rates.
+    PityReset,
+    /// Move on to a new session after pulling any 5★.
+    AnyFivestar,
+    /// Never spend more than this many orbs on one session, other than on its first summon.
+    SessionOrbCap(u32),
+}
+
+impl StopPolicy {
+    /// Whether to stop pulling the current session after pulling a stone from `pool`, which
+    /// changed the pity count from `pity_before` to what it is in `status`.
+    pub fn ends_session(
+        &self,
+        pool: Pool,
+        pity_before: u32,
+        status: &Status,
+        rate_model: &RateModel,
+    ) -> bool {
+        match self {
+            StopPolicy::PityReset => {
+                rate_model.pity_steps(pity_before) > 0
+                    && rate_model.pity_steps(status.pity_count) == 0
+            }
+            StopPolicy::AnyFivestar => matches!(pool, Pool::Focus | Pool::Fivestar),
+            _ => false,
+        }
+    }
+
+    /// Whether to pull another stone costing `cost` orbs, with `session_orbs` already spent on
+    /// the current session.
+    pub fn allows_cost(&self, session_orbs: u32, cost: u32) -> bool {
+        match self {
+            StopPolicy::SessionOrbCap(cap) => session_orbs + cost <= *cap,
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use crate::{
+        banner::StandardBanner,
+        goal::{BudgetGoalLimit, Goal},
+        sim::{Sim, SimConfig},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_fallback() {
+        use Color::*;
+        let banner = StandardBanner::Standard {
+            focus: [1, 1, 1, 1],
+        }
+        .as_generic_banner(false);
+        let status = Status::default();
+        let goal = UnitCountGoal::new(
+            vec![UnitGoal {
+                color: Red,
+                copies: 1,
+                pools: EnumSet::from(Pool::Focus),
+                min_rarity: Default::default(),
+            }],
+            true,
+        );
+        let strategy = ColorStrategy::GoalColorsOrFallback(vec![Colorless, Green]);
+        let pulls = |colors: [Color; 5]| {
+            let session = colors.map(|color| (Pool::Common, color));
+            (0..5)
+                .filter(|&i| {
+                    let state = SessionState {
+                        banner: &banner,
+                        session: &session,
+                        num_pulled: 0,
+                        status: &status,
+                        goal_colors: EnumSet::from(Red),
+                        goal: GoalProgress::Quantity(&goal),
+                    };
+                    strategy.should_pull(&state, i)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(pulls([Blue, Red, Green, Red, Blue]), vec![1, 3]);
+        assert_eq!(pulls([Blue, Green, Colorless, Green, Colorless]), vec![2]);
+        assert_eq!(pulls([Blue, Green, Blue, Green, Blue]), vec![1]);
+        assert_eq!(pulls([Blue, Blue, Blue, Blue, Blue]), Vec::<usize>::new());
+    }
+
+    /// Pulls the goal's colors, checking that the progress it's shown matches them.
+    #[derive(Debug)]
+    struct CheckProgress;
+
+    impl PullStrategy for CheckProgress {
+        fn should_pull(&self, state: &SessionState, index: usize) -> bool {
+            match state.goal {
+                GoalProgress::Quantity(goal) => {
+                    let needed = goal
+                        .units
+                        .iter()
+                        .filter(|unit| unit.copies > 0)
+                        .map(|unit| unit.color)
+                        .collect::<EnumSet<_>>();
+                    assert_eq!(needed, state.goal_colors);
+                }
+                GoalProgress::OrbBudget { goal, copies } => {
+                    assert_eq!(goal.units.len(), copies.len());
+                }
+            }
+            state.goal_colors.contains(state.session[index].1)
+        }
+    }
+
+    #[test]
+    fn test_goal_progress() {
+        let banner = StandardBanner::Standard {
+            focus: [1, 1, 1, 1],
+        }
+        .as_generic_banner(false);
+        let unit = |color, copies| UnitGoal {
+            color,
+            copies,
+            pools: EnumSet::from(Pool::Focus),
+            min_rarity: Default::default(),
+        };
+        let config = SimConfig {
+            strategy: Arc::new(CheckProgress),
+            ..Default::default()
+        };
+        let goal = UnitCountGoal::new(vec![unit(Color::Red, 1), unit(Color::Blue, 2)], true);
+        Sim::new(banner.clone(), Goal::Quantity(goal))
+            .with_config(config.clone())
+            .sim(100);
+        let goal = BudgetGoal::whole_banner(&banner, BudgetGoalLimit::OrbCount(200));
+        Sim::new(banner, Goal::OrbBudget(goal))
+            .with_config(config)
+            .sim(100);
+    }
+}
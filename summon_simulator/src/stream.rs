@@ -0,0 +1,193 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use instant::Instant;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    frequency_counter::FrequencyCounter,
+    sim::{Sim, StoppingRule},
+};
+
+/// How often a [`SimStream`] gives a snapshot of the results.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Granularity {
+    /// After every this many trials.
+    Samples(u32),
+    /// After about this much time has passed.
+    Time(Duration),
+}
+
+/// Stops a [`SimStream`] from somewhere else, e.g. another thread or a UI callback.
+#[derive(Clone, Debug, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Runs a [`Sim`] a bit at a time, yielding the results so far after each bit. The stream ends
+/// when it's cancelled or its stopping rule is satisfied, and otherwise runs forever.
+pub struct SimStream {
+    sim: Sim,
+    granularity: Granularity,
+    rule: Option<StoppingRule>,
+    cancel: CancelHandle,
+    /// Trials per chunk when aiming for a time granularity.
+    chunk_size: u32,
+    finished: bool,
+}
+
+impl SimStream {
+    pub fn new(sim: Sim, granularity: Granularity) -> Self {
+        Self {
+            sim,
+            granularity,
+            rule: None,
+            cancel: CancelHandle::default(),
+            chunk_size: 100,
+            finished: false,
+        }
+    }
+
+    /// Ends the stream once `rule` is satisfied.
+    pub fn with_stopping_rule(mut self, rule: StoppingRule) -> Self {
+        self.rule = Some(rule);
+        self
+    }
+
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Whether the stopping rule has been satisfied.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Confidence intervals of the stopping rule's percentiles, or nothing without a rule.
+    pub fn percentile_intervals(&self) -> Vec<(u32, u32)> {
+        self.rule
+            .as_ref()
+            .map(|rule| self.sim.percentile_intervals(rule))
+            .unwrap_or_default()
+    }
+
+    pub fn sim(&self) -> &Sim {
+        &self.sim
+    }
+
+    pub fn into_sim(self) -> Sim {
+        self.sim
+    }
+
+    fn run(&mut self, iters: u32) {
+        let iters = match &self.rule {
+            Some(rule) => iters.min(rule.max_iters.saturating_sub(self.sim.data().total())),
+            None => iters,
+        };
+        self.sim.sim(iters.max(1));
+        if let Some(rule) = &self.rule {
+            self.finished = rule.is_satisfied(self.sim.data());
+        }
+    }
+}
+
+impl Iterator for SimStream {
+    /// The results of every trial so far.
+    type Item = FrequencyCounter;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished || self.cancel.is_cancelled() {
+            return None;
+        }
+        match self.granularity {
+            Granularity::Samples(samples) => self.run(samples),
+            Granularity::Time(interval) => {
+                let start = Instant::now();
+                loop {
+                    let chunk_start = Instant::now();
+                    self.run(self.chunk_size);
+                    let duration = Instant::now().duration_since(chunk_start);
+                    // Aim the next chunk at the requested interval...
+                    let new_size = (self.chunk_size as f64 * interval.as_secs_f64()
+                        / duration.as_secs_f64().max(1e-6))
+                    .round() as u32;
+                    // ...but don't ramp up more than 10x each time, to avoid a very fast first
+                    // chunk resulting in a very slow second
+                    self.chunk_size = new_size.clamp(1, self.chunk_size.saturating_mul(10));
+                    // A snapshot after a very quick chunk has much less data than the following
+                    // ones will, so keep going until a reasonable fraction of the interval
+                    if self.finished
+                        || self.cancel.is_cancelled()
+                        || Instant::now().duration_since(start) * 2 > interval
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+        Some(self.sim.data().clone())
+    }
+}
+
+impl Sim {
+    /// Turns the simulation into a stream of snapshots of its results.
+    pub fn stream(self, granularity: Granularity) -> SimStream {
+        SimStream::new(self, granularity)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{goal::Goal, sim::test_banner_and_goal};
+
+    use super::*;
+
+    fn sim() -> Sim {
+        let (banner, goal) = test_banner_and_goal();
+        Sim::new(banner, Goal::Quantity(goal))
+    }
+
+    #[test]
+    fn test_stream() {
+        let totals = sim()
+            .stream(Granularity::Samples(1000))
+            .take(3)
+            .map(|data| data.total())
+            .collect::<Vec<_>>();
+        assert_eq!(totals, vec![1000, 2000, 3000]);
+
+        let mut stream = sim().stream(Granularity::Time(Duration::from_millis(20)));
+        let first = stream.next().unwrap().total();
+        let second = stream.next().unwrap().total();
+        assert!(first > 0 && second > first);
+        stream.cancel_handle().cancel();
+        assert_eq!(stream.next(), None);
+        assert_eq!(stream.into_sim().data().total(), second);
+
+        let rule = StoppingRule {
+            max_width: 0,
+            max_iters: 2500,
+            ..Default::default()
+        };
+        let mut stream = sim()
+            .stream(Granularity::Samples(1000))
+            .with_stopping_rule(rule.clone());
+        let totals = stream.by_ref().map(|data| data.total()).collect::<Vec<_>>();
+        assert_eq!(totals, vec![1000, 2000, 2500]);
+        assert!(stream.is_finished());
+        assert_eq!(stream.percentile_intervals().len(), rule.percentiles.len());
+    }
+}
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    observer::Observer,
+    sim::Status,
+    types::{Color, Pool},
+};
+
+/// Everything that happened in one summoning session of a traced trial.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionTrace {
+    pub stones: [(Pool, Color); 5],
+    /// Indices into `stones` of the stones that were pulled, in order.
+    pub pulled: Vec<usize>,
+    pub status_before: Status,
+    pub status_after: Status,
+    /// Indices of the goal units that sparks were used on during the session, in order.
+    pub sparked_units: Vec<usize>,
+    /// Copies still needed of each goal unit after the session for quantity goals, or copies
+    /// pulled so far for orb budget goals.
+    pub goal_copies: Vec<u32>,
+}
+
+/// The full history of a single trial.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrialTrace {
+    /// Seed of the block of trials the trial was run in. Running the same block again with this
+    /// seed reproduces the trial.
+    pub seed: u64,
+    /// Position of the trial within its block.
+    pub trial: u32,
+    /// Indices of the goal units that sparks were used on before the first session, with sparks
+    /// the start state had already earned.
+    pub start_sparked_units: Vec<usize>,
+    pub sessions: Vec<SessionTrace>,
+    /// The value recorded for the trial: orbs spent for quantity goals, copies pulled for orb
+    /// budget goals.
+    pub result: u32,
+}
+
+#[derive(Default)]
+pub(crate) struct TraceRecorder {
+    pub start_sparked_units: Vec<usize>,
+    pub sessions: Vec<SessionTrace>,
+}
+
+impl Observer for TraceRecorder {
+    fn session_start(&mut self, stones: &[(Pool, Color); 5], status: &Status) {
+        self.sessions.push(SessionTrace {
+            stones: *stones,
+            pulled: Vec::new(),
+            status_before: *status,
+            status_after: *status,
+            sparked_units: Vec::new(),
+            goal_copies: Vec::new(),
+        });
+    }
+
+    fn pulled(&mut self, index: usize, _stone: (Pool, Color), _status: &Status) {
+        if let Some(session) = self.sessions.last_mut() {
+            session.pulled.push(index);
+        }
+    }
+
+    fn spark_used(&mut self, unit: usize) {
+        match self.sessions.last_mut() {
+            Some(session) => session.sparked_units.push(unit),
+            None => self.start_sparked_units.push(unit),
+        }
+    }
+
+    fn session_end(&mut self, status: &Status, goal_copies: impl FnOnce() -> Vec<u32>) {
+        if let Some(session) = self.sessions.last_mut() {
+            session.status_after = *status;
+            session.goal_copies = goal_copies();
+        }
+    }
+}
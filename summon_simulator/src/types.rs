@@ -23,6 +23,8 @@ impl TryFrom<usize> for Color {
     }
 }
 
+/// The kinds of pool that units come from, which is how goals pick units. A banner can have
+/// several pools of the same kind, see [`crate::banner::GenericBanner::pools`].
 #[derive(Hash, Debug, PartialOrd, Ord, EnumSetType, Serialize, Deserialize)]
 pub enum Pool {
     Focus,
@@ -46,3 +48,24 @@ impl TryFrom<usize> for Pool {
         })
     }
 }
+
+/// How many stars a pulled unit has.
+#[derive(Hash, Debug, Default, PartialOrd, Ord, EnumSetType, Serialize, Deserialize)]
+pub enum Rarity {
+    #[default]
+    ThreeStar,
+    FourStar,
+    FiveStar,
+}
+
+impl Pool {
+    /// The rarity of every unit pulled from the pool, or `None` for the common pool, whose units
+    /// can be either 3★ or 4★.
+    pub fn fixed_rarity(&self) -> Option<Rarity> {
+        match self {
+            Pool::Focus | Pool::Fivestar => Some(Rarity::FiveStar),
+            Pool::FourstarFocus | Pool::FourstarSpecial => Some(Rarity::FourStar),
+            Pool::Common => None,
+        }
+    }
+}
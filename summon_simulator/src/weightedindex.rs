@@ -1,6 +1,6 @@
 use rand::prelude::Distribution;
 
-use crate::types::{Color, Pool};
+use crate::types::Color;
 
 pub struct WeightedIndexColor {
     thresholds: [u32; 3],
@@ -38,41 +38,37 @@ impl Distribution<Color> for WeightedIndexColor {
     }
 }
 
+/// Picks which of a banner's pools a stone comes from, as an index into the list of pools.
 pub struct WeightedIndexPool {
-    thresholds: [u32; 4],
+    thresholds: Box<[u32]>,
 }
 
 impl WeightedIndexPool {
     pub fn new(values: impl IntoIterator<Item = impl Into<f32>>) -> Self {
         let values = values.into_iter().map(|i| i.into()).collect::<Vec<_>>();
         let sum = values.iter().sum::<f32>();
-        debug_assert!(values.len() == 5);
+        debug_assert!(!values.is_empty());
+        let mut cum_total = 0.0;
         Self {
-            thresholds: [
-                ((values[0] / sum) * u32::MAX as f32) as u32,
-                (((values[0] + values[1]) / sum) * u32::MAX as f32) as u32,
-                (((values[0] + values[1] + values[2]) / sum) * u32::MAX as f32) as u32,
-                (((values[0] + values[1] + values[2] + values[3]) / sum) * u32::MAX as f32) as u32,
-            ],
+            thresholds: values[..values.len() - 1]
+                .iter()
+                .map(|value| {
+                    cum_total += value;
+                    ((cum_total / sum) * u32::MAX as f32) as u32
+                })
+                .collect(),
         }
     }
 }
 
-impl Distribution<Pool> for WeightedIndexPool {
-    fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Pool {
+impl Distribution<usize> for WeightedIndexPool {
+    fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> usize {
         let choice = rng.gen::<u32>();
-        if choice > self.thresholds[3] {
-            Pool::Common
-        } else if choice < self.thresholds[1] {
-            if choice < self.thresholds[0] {
-                Pool::Focus
-            } else {
-                Pool::Fivestar
-            }
-        } else if choice < self.thresholds[2] {
-            Pool::FourstarFocus
-        } else {
-            Pool::FourstarSpecial
-        }
+        // The thresholds only go up, so the pool is the number of them that were passed. Counting
+        // them all avoids a branch for each pool.
+        self.thresholds
+            .iter()
+            .map(|&threshold| (choice >= threshold) as usize)
+            .sum()
     }
 }